@@ -0,0 +1,845 @@
+use crate::{
+    calc::{self, parse_number},
+    error::AssembleError,
+    lexer::{Token, tokenize},
+};
+use rsc8_core::{
+    chip8::{PROGRAM_START, XO_MEMORY_SIZE},
+    instruction::{Instruction, LONG_OPCODE_PREFIX},
+};
+use std::collections::{HashMap, VecDeque};
+
+// Guards against macros that expand themselves forever
+const MAX_MACRO_EXPANSIONS: usize = 100_000;
+
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssembleError> {
+    let mut assembler = Assembler::new(tokenize(source));
+    while let Some(token) = assembler.tokens.pop_front() {
+        assembler.statement(token)?;
+    }
+    assembler.finish()
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Token>,
+}
+
+enum Fixup {
+    // Low 12 bits of the instruction at the address
+    Nnn,
+    // The whole word at the address, after F000
+    Long,
+    // Low nibble of the byte after the address
+    UnpackHigh,
+    // The byte after the address
+    UnpackLow,
+}
+
+enum Block {
+    If {
+        jump: usize,
+        token: Token,
+    },
+    Else {
+        jump: usize,
+        token: Token,
+    },
+    Loop {
+        start: usize,
+        whiles: Vec<usize>,
+        token: Token,
+    },
+}
+
+struct Assembler {
+    tokens: VecDeque<Token>,
+    last: Token,
+    memory: Vec<u8>,
+    written: Vec<bool>,
+    here: usize,
+    main_jump: Option<usize>,
+    constants: HashMap<String, f64>,
+    labels: HashMap<String, usize>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<(usize, Token, Fixup)>,
+    blocks: Vec<Block>,
+    expansions: usize,
+}
+
+impl Assembler {
+    fn new(tokens: Vec<Token>) -> Self {
+        let last = tokens.last().cloned().unwrap_or(Token {
+            text: String::new(),
+            line: 1,
+            column: 1,
+        });
+        // Execution starts at 0x200, so reserve a jump to main unless it is already there
+        let starts_with_main =
+            tokens.len() >= 2 && tokens[0].text == ":" && tokens[1].text == "main";
+        let main_jump = (!starts_with_main).then_some(PROGRAM_START as usize);
+        Self {
+            tokens: tokens.into(),
+            last,
+            memory: vec![0; XO_MEMORY_SIZE],
+            written: vec![false; XO_MEMORY_SIZE],
+            here: PROGRAM_START as usize + if starts_with_main { 0 } else { 2 },
+            main_jump,
+            constants: HashMap::new(),
+            labels: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            blocks: Vec::new(),
+            expansions: 0,
+        }
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, AssembleError> {
+        if let Some(block) = self.blocks.pop() {
+            return Err(match block {
+                Block::If { token, .. } | Block::Else { token, .. } => {
+                    AssembleError::at(&token, "'if' is missing its 'end'")
+                }
+                Block::Loop { token, .. } => {
+                    AssembleError::at(&token, "'loop' is missing its 'again'")
+                }
+            });
+        }
+
+        for (address, label, fixup) in std::mem::take(&mut self.fixups) {
+            let target = *self.labels.get(&label.text).ok_or_else(|| {
+                AssembleError::at(&label, format!("undefined label '{}'", label.text))
+            })?;
+            match fixup {
+                Fixup::Nnn => self.patch_nnn(&label, address, target)?,
+                Fixup::Long => {
+                    self.memory[address] = (target >> 8) as u8;
+                    self.memory[address + 1] = target as u8;
+                }
+                Fixup::UnpackHigh => self.memory[address + 1] |= (target >> 8) as u8 & 0x0F,
+                Fixup::UnpackLow => self.memory[address + 1] = target as u8,
+            }
+        }
+
+        let main = self.labels.get("main").copied();
+        if let Some(address) = self.main_jump {
+            let main =
+                main.ok_or_else(|| AssembleError::at(&self.first(), "missing ': main' label"))?;
+            let jump = u16::from(Instruction::Ins1NNN(0));
+            self.memory[address..address + 2].copy_from_slice(&jump.to_be_bytes());
+            self.written[address..address + 2].fill(true);
+            self.patch_nnn(&self.first(), address, main)?;
+        }
+
+        let end = self
+            .written
+            .iter()
+            .rposition(|&written| written)
+            .map_or(0, |last| last + 1);
+        let start = PROGRAM_START as usize;
+        Ok(self.memory[start..end.max(start)].to_vec())
+    }
+
+    fn first(&self) -> Token {
+        Token {
+            text: String::new(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn statement(&mut self, token: Token) -> Result<(), AssembleError> {
+        match token.text.as_str() {
+            ":" => {
+                let name = self.name()?;
+                if self.labels.contains_key(&name.text) {
+                    return Err(AssembleError::at(
+                        &name,
+                        format!("label '{}' is already defined", name.text),
+                    ));
+                }
+                self.labels.insert(name.text, self.here);
+            }
+            ":const" => {
+                let name = self.name()?;
+                let value = self.value()?;
+                self.constants.insert(name.text, value);
+            }
+            ":calc" => {
+                let name = self.name()?;
+                let value = self.calc()?;
+                self.constants.insert(name.text, value);
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let register = self.register()?;
+                self.aliases.insert(name.text, register);
+            }
+            ":macro" => self.define_macro()?,
+            ":org" => {
+                let value = self.value()? as i64;
+                if !(PROGRAM_START as i64..XO_MEMORY_SIZE as i64).contains(&value) {
+                    return Err(AssembleError::at(
+                        &token,
+                        format!("cannot :org to {value:#x}"),
+                    ));
+                }
+                self.here = value as usize;
+            }
+            ":byte" => {
+                let byte = self.byte_value(&token)?;
+                self.emit_byte(&token, byte)?;
+            }
+            ":call" => {
+                let target = self.address(Fixup::Nnn)?;
+                self.emit(&token, Instruction::Ins2NNN(target))?;
+            }
+            ":unpack" => {
+                // v0 := nibble << 4 | address >> 8, v1 := address & 0xFF
+                let nibble = self.nibble()?;
+                let label = self.next()?;
+                let high = self.address_of(&label, Fixup::UnpackHigh)?;
+                self.emit(
+                    &token,
+                    Instruction::Ins6XNN(0, nibble << 4 | (high >> 8) as u8 & 0x0F),
+                )?;
+                let low = self.address_of(&label, Fixup::UnpackLow)?;
+                self.emit(&token, Instruction::Ins6XNN(1, low as u8))?;
+            }
+            "clear" => self.emit(&token, Instruction::Ins00E0)?,
+            "return" | ";" => self.emit(&token, Instruction::Ins00EE)?,
+            "exit" => self.emit(&token, Instruction::Ins00FD)?,
+            "lores" => self.emit(&token, Instruction::Ins00FE)?,
+            "hires" => self.emit(&token, Instruction::Ins00FF)?,
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit(&token, Instruction::Ins00CN(n))?;
+            }
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.emit(&token, Instruction::Ins00DN(n))?;
+            }
+            "scroll-right" => self.emit(&token, Instruction::Ins00FB)?,
+            "scroll-left" => self.emit(&token, Instruction::Ins00FC)?,
+            "jump" => {
+                let target = self.address(Fixup::Nnn)?;
+                self.emit(&token, Instruction::Ins1NNN(target))?;
+            }
+            "jump0" => {
+                let target = self.address(Fixup::Nnn)?;
+                self.emit(&token, Instruction::InsBNNN(target))?;
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.emit(&token, Instruction::InsDXYN(x, y, n))?;
+            }
+            "plane" => {
+                let n = self.nibble()?;
+                self.emit(&token, Instruction::InsFN01(n))?;
+            }
+            "audio" => self.emit(&token, Instruction::InsF002)?,
+            "bcd" => {
+                let x = self.register()?;
+                self.emit(&token, Instruction::InsFX33(x))?;
+            }
+            "save" | "load" => {
+                let x = self.register()?;
+                let instruction = if self.tokens.front().is_some_and(|next| next.text == "-") {
+                    self.tokens.pop_front();
+                    let y = self.register()?;
+                    match token.text.as_str() {
+                        "save" => Instruction::Ins5XY2(x, y),
+                        _ => Instruction::Ins5XY3(x, y),
+                    }
+                } else {
+                    match token.text.as_str() {
+                        "save" => Instruction::InsFX55(x),
+                        _ => Instruction::InsFX65(x),
+                    }
+                };
+                self.emit(&token, instruction)?;
+            }
+            "saveflags" => {
+                let x = self.register()?;
+                self.emit(&token, Instruction::InsFX75(x))?;
+            }
+            "loadflags" => {
+                let x = self.register()?;
+                self.emit(&token, Instruction::InsFX85(x))?;
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                let instruction = match token.text.as_str() {
+                    "delay" => Instruction::InsFX15(x),
+                    "buzzer" => Instruction::InsFX18(x),
+                    _ => Instruction::InsFX3A(x),
+                };
+                self.emit(&token, instruction)?;
+            }
+            "i" => self.i_statement(&token)?,
+            "if" => {
+                let (skip_unless, skip_if) = self.condition()?;
+                let keyword = self.next()?;
+                match keyword.text.as_str() {
+                    "then" => self.emit(&token, skip_unless)?,
+                    "begin" => {
+                        self.emit(&token, skip_if)?;
+                        let jump = self.here;
+                        self.emit(&token, Instruction::Ins1NNN(0))?;
+                        self.blocks.push(Block::If {
+                            jump,
+                            token: token.clone(),
+                        });
+                    }
+                    _ => return Err(AssembleError::at(&keyword, "expected 'then' or 'begin'")),
+                }
+            }
+            "else" => {
+                let Some(Block::If { jump, .. }) = self.blocks.pop() else {
+                    return Err(AssembleError::at(&token, "'else' without 'if ... begin'"));
+                };
+                let end_jump = self.here;
+                self.emit(&token, Instruction::Ins1NNN(0))?;
+                self.patch_nnn(&token, jump, self.here)?;
+                self.blocks.push(Block::Else {
+                    jump: end_jump,
+                    token: token.clone(),
+                });
+            }
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump, .. } | Block::Else { jump, .. }) => {
+                    self.patch_nnn(&token, jump, self.here)?
+                }
+                _ => return Err(AssembleError::at(&token, "'end' without 'if ... begin'")),
+            },
+            "loop" => self.blocks.push(Block::Loop {
+                start: self.here,
+                whiles: Vec::new(),
+                token: token.clone(),
+            }),
+            "while" => {
+                let (_, skip_if) = self.condition()?;
+                self.emit(&token, skip_if)?;
+                let jump = self.here;
+                self.emit(&token, Instruction::Ins1NNN(0))?;
+                match self.blocks.iter_mut().rev().find_map(|block| match block {
+                    Block::Loop { whiles, .. } => Some(whiles),
+                    _ => None,
+                }) {
+                    Some(whiles) => whiles.push(jump),
+                    None => return Err(AssembleError::at(&token, "'while' outside of 'loop'")),
+                }
+            }
+            "again" => {
+                let Some(Block::Loop { start, whiles, .. }) = self.blocks.pop() else {
+                    return Err(AssembleError::at(&token, "'again' without 'loop'"));
+                };
+                self.emit(&token, Instruction::Ins1NNN(start as u16))?;
+                for jump in whiles {
+                    self.patch_nnn(&token, jump, self.here)?;
+                }
+            }
+            text => {
+                if let Some(x) = self.register_of(&token) {
+                    return self.register_statement(&token, x);
+                }
+                if self.macros.contains_key(text) {
+                    return self.expand_macro(&token);
+                }
+                if parse_number(text).is_some() || self.constants.contains_key(text) {
+                    // Bare numbers are raw data, typically sprites
+                    let byte = self.byte_of(&token)?;
+                    return self.emit_byte(&token, byte);
+                }
+                if !is_name(text) {
+                    return Err(AssembleError::at(&token, format!("unexpected '{text}'")));
+                }
+                // A bare label is a subroutine call
+                let target = self.address_of(&token, Fixup::Nnn)?;
+                self.emit(&token, Instruction::Ins2NNN(target))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn register_statement(&mut self, token: &Token, x: u8) -> Result<(), AssembleError> {
+        let operator = self.next()?;
+        let operand = self.next()?;
+        let y = self.register_of(&operand);
+        let instruction = match (operator.text.as_str(), y) {
+            (":=", Some(y)) => Instruction::Ins8XY0(x, y),
+            (":=", None) => match operand.text.as_str() {
+                "key" => Instruction::InsFX0A(x),
+                "delay" => Instruction::InsFX07(x),
+                "random" => Instruction::InsCXNN(x, self.byte()?),
+                _ => Instruction::Ins6XNN(x, self.byte_of(&operand)?),
+            },
+            ("+=", Some(y)) => Instruction::Ins8XY4(x, y),
+            ("+=", None) => Instruction::Ins7XNN(x, self.byte_of(&operand)?),
+            ("-=", Some(y)) => Instruction::Ins8XY5(x, y),
+            ("-=", None) => Instruction::Ins7XNN(x, self.byte_of(&operand)?.wrapping_neg()),
+            ("|=", Some(y)) => Instruction::Ins8XY1(x, y),
+            ("&=", Some(y)) => Instruction::Ins8XY2(x, y),
+            ("^=", Some(y)) => Instruction::Ins8XY3(x, y),
+            (">>=", Some(y)) => Instruction::Ins8XY6(x, y),
+            ("=-", Some(y)) => Instruction::Ins8XY7(x, y),
+            ("<<=", Some(y)) => Instruction::Ins8XYE(x, y),
+            ("|=" | "&=" | "^=" | ">>=" | "=-" | "<<=", None) => {
+                return Err(AssembleError::at(&operand, "expected a register"));
+            }
+            _ => {
+                return Err(AssembleError::at(
+                    &operator,
+                    format!("unknown operator '{}'", operator.text),
+                ));
+            }
+        };
+        self.emit(token, instruction)
+    }
+
+    fn i_statement(&mut self, token: &Token) -> Result<(), AssembleError> {
+        let operator = self.next()?;
+        let instruction = match operator.text.as_str() {
+            "+=" => Instruction::InsFX1E(self.register()?),
+            ":=" => match self.tokens.front().map(|next| next.text.as_str()) {
+                Some("hex") => {
+                    self.tokens.pop_front();
+                    Instruction::InsFX29(self.register()?)
+                }
+                Some("bighex") => {
+                    self.tokens.pop_front();
+                    Instruction::InsFX30(self.register()?)
+                }
+                Some("long") => {
+                    self.tokens.pop_front();
+                    let operand = self.next()?;
+                    // The address word follows the F000 prefix
+                    self.emit_word(token, LONG_OPCODE_PREFIX)?;
+                    let target = self.address_of(&operand, Fixup::Long)?;
+                    return self.emit_word(token, target);
+                }
+                _ => Instruction::InsANNN(self.address(Fixup::Nnn)?),
+            },
+            _ => {
+                return Err(AssembleError::at(
+                    &operator,
+                    format!("unknown operator '{}'", operator.text),
+                ));
+            }
+        };
+        self.emit(token, instruction)
+    }
+
+    // Returns the skip that runs the next instruction only if the condition
+    // holds, and the one that runs it only if it does not
+    fn condition(&mut self) -> Result<(Instruction, Instruction), AssembleError> {
+        let x = self.register()?;
+        let operator = self.next()?;
+        match operator.text.as_str() {
+            "key" => Ok((Instruction::InsEXA1(x), Instruction::InsEX9E(x))),
+            "-key" => Ok((Instruction::InsEX9E(x), Instruction::InsEXA1(x))),
+            "==" | "!=" => {
+                let operand = self.next()?;
+                let (equal, not_equal) = match self.register_of(&operand) {
+                    Some(y) => (Instruction::Ins9XY0(x, y), Instruction::Ins5XY0(x, y)),
+                    None => {
+                        let nn = self.byte_of(&operand)?;
+                        (Instruction::Ins4XNN(x, nn), Instruction::Ins3XNN(x, nn))
+                    }
+                };
+                Ok(if operator.text == "==" {
+                    (equal, not_equal)
+                } else {
+                    (not_equal, equal)
+                })
+            }
+            text => Err(AssembleError::at(
+                &operator,
+                format!("unsupported comparison '{text}'"),
+            )),
+        }
+    }
+
+    fn define_macro(&mut self) -> Result<(), AssembleError> {
+        let name = self.name()?;
+        let mut params = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token.text == "{" {
+                break;
+            }
+            params.push(token.text);
+        }
+        let body = self.braced()?;
+        self.macros.insert(name.text, Macro { params, body });
+        Ok(())
+    }
+
+    fn expand_macro(&mut self, token: &Token) -> Result<(), AssembleError> {
+        self.expansions += 1;
+        if self.expansions > MAX_MACRO_EXPANSIONS {
+            return Err(AssembleError::at(token, "too many macro expansions"));
+        }
+        let params = self.macros[&token.text].params.clone();
+        let mut args = HashMap::new();
+        for param in params {
+            let arg = self.next()?;
+            args.insert(param, arg.text);
+        }
+        let expanded: Vec<_> = self.macros[&token.text]
+            .body
+            .iter()
+            .map(|body_token| Token {
+                text: args
+                    .get(&body_token.text)
+                    .unwrap_or(&body_token.text)
+                    .clone(),
+                ..body_token.clone()
+            })
+            .collect();
+        for body_token in expanded.into_iter().rev() {
+            self.tokens.push_front(body_token);
+        }
+        Ok(())
+    }
+
+    // Tokens up to the matching `}`, the opening `{` already consumed
+    fn braced(&mut self) -> Result<Vec<Token>, AssembleError> {
+        let mut depth = 0;
+        let mut tokens = Vec::new();
+        loop {
+            let token = self.next()?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" if depth == 0 => return Ok(tokens),
+                "}" => depth -= 1,
+                _ => {}
+            }
+            tokens.push(token);
+        }
+    }
+
+    fn calc(&mut self) -> Result<f64, AssembleError> {
+        self.expect("{")?;
+        let tokens = self.braced()?;
+        let end = tokens.last().cloned().unwrap_or_else(|| self.last.clone());
+        calc::evaluate(&tokens, &end, |token| self.lookup(token))
+    }
+
+    // A single number or name, or a braced `:calc` expression
+    fn value(&mut self) -> Result<f64, AssembleError> {
+        if self.tokens.front().is_some_and(|next| next.text == "{") {
+            return self.calc();
+        }
+        let token = self.next()?;
+        self.lookup_number(&token)
+    }
+
+    fn lookup_number(&self, token: &Token) -> Result<f64, AssembleError> {
+        match parse_number(&token.text) {
+            Some(value) => Ok(value as f64),
+            None => self.lookup(token),
+        }
+    }
+
+    fn lookup(&self, token: &Token) -> Result<f64, AssembleError> {
+        if token.text == "HERE" {
+            return Ok(self.here as f64);
+        }
+        self.constants
+            .get(&token.text)
+            .copied()
+            .or_else(|| self.labels.get(&token.text).map(|&address| address as f64))
+            .ok_or_else(|| AssembleError::at(token, format!("undefined name '{}'", token.text)))
+    }
+
+    fn byte(&mut self) -> Result<u8, AssembleError> {
+        let token = self.next()?;
+        self.byte_of(&token)
+    }
+
+    fn byte_value(&mut self, token: &Token) -> Result<u8, AssembleError> {
+        let value = self.value()? as i64;
+        to_byte(token, value)
+    }
+
+    fn byte_of(&self, token: &Token) -> Result<u8, AssembleError> {
+        let value = self.lookup_number(token)? as i64;
+        to_byte(token, value)
+    }
+
+    fn nibble(&mut self) -> Result<u8, AssembleError> {
+        let token = self.next()?;
+        match self.lookup_number(&token)? as i64 {
+            value @ 0..=15 => Ok(value as u8),
+            value => Err(AssembleError::at(
+                &token,
+                format!("{value} does not fit in a nibble"),
+            )),
+        }
+    }
+
+    fn address(&mut self, fixup: Fixup) -> Result<u16, AssembleError> {
+        let token = self.next()?;
+        self.address_of(&token, fixup)
+    }
+
+    // Labels that are not defined yet are patched in once assembly finishes
+    fn address_of(&mut self, token: &Token, fixup: Fixup) -> Result<u16, AssembleError> {
+        let limit = match fixup {
+            Fixup::Nnn => 0x0FFF,
+            _ => 0xFFFF,
+        };
+        if parse_number(&token.text).is_none()
+            && !self.constants.contains_key(&token.text)
+            && !self.labels.contains_key(&token.text)
+        {
+            if !is_name(&token.text) {
+                return Err(AssembleError::at(
+                    token,
+                    format!("expected an address, found '{}'", token.text),
+                ));
+            }
+            self.fixups.push((self.here, token.clone(), fixup));
+            return Ok(0);
+        }
+        match self.lookup_number(token)? as i64 {
+            value if (0..=limit).contains(&value) => Ok(value as u16),
+            value => Err(AssembleError::at(
+                token,
+                format!("address {value:#x} is out of range"),
+            )),
+        }
+    }
+
+    fn register(&mut self) -> Result<u8, AssembleError> {
+        let token = self.next()?;
+        self.register_of(&token).ok_or_else(|| {
+            AssembleError::at(
+                &token,
+                format!("expected a register, found '{}'", token.text),
+            )
+        })
+    }
+
+    fn register_of(&self, token: &Token) -> Option<u8> {
+        if let Some(&register) = self.aliases.get(&token.text) {
+            return Some(register);
+        }
+        let digit = token.text.strip_prefix(['v', 'V'])?;
+        if digit.len() != 1 {
+            return None;
+        }
+        u8::from_str_radix(digit, 16).ok()
+    }
+
+    fn name(&mut self) -> Result<Token, AssembleError> {
+        let token = self.next()?;
+        if !is_name(&token.text) || self.register_of(&token).is_some() {
+            return Err(AssembleError::at(
+                &token,
+                format!("invalid name '{}'", token.text),
+            ));
+        }
+        Ok(token)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AssembleError> {
+        let token = self.next()?;
+        if token.text != text {
+            return Err(AssembleError::at(
+                &token,
+                format!("expected '{text}', found '{}'", token.text),
+            ));
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Token, AssembleError> {
+        self.tokens
+            .pop_front()
+            .ok_or_else(|| AssembleError::at(&self.last, "unexpected end of file"))
+    }
+
+    fn emit(&mut self, token: &Token, instruction: Instruction) -> Result<(), AssembleError> {
+        self.emit_word(token, u16::from(&instruction))?;
+        if let Instruction::InsF000(nnnn) = instruction {
+            self.emit_word(token, nnnn)?;
+        }
+        Ok(())
+    }
+
+    fn emit_word(&mut self, token: &Token, word: u16) -> Result<(), AssembleError> {
+        let [high, low] = word.to_be_bytes();
+        self.emit_byte(token, high)?;
+        self.emit_byte(token, low)
+    }
+
+    fn emit_byte(&mut self, token: &Token, byte: u8) -> Result<(), AssembleError> {
+        if self.here >= XO_MEMORY_SIZE {
+            return Err(AssembleError::at(token, "program does not fit in memory"));
+        }
+        if self.written[self.here] {
+            return Err(AssembleError::at(
+                token,
+                format!("data overlaps at {:#05x}", self.here),
+            ));
+        }
+        self.memory[self.here] = byte;
+        self.written[self.here] = true;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn patch_nnn(
+        &mut self,
+        token: &Token,
+        address: usize,
+        target: usize,
+    ) -> Result<(), AssembleError> {
+        if target > 0x0FFF {
+            return Err(AssembleError::at(
+                token,
+                format!("address {target:#x} is out of range"),
+            ));
+        }
+        self.memory[address] = self.memory[address] & 0xF0 | (target >> 8) as u8;
+        self.memory[address + 1] = target as u8;
+        Ok(())
+    }
+}
+
+fn to_byte(token: &Token, value: i64) -> Result<u8, AssembleError> {
+    match value {
+        -128..=255 => Ok(value as u8),
+        _ => Err(AssembleError::at(
+            token,
+            format!("{value} does not fit in a byte"),
+        )),
+    }
+}
+
+fn is_name(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::assemble;
+
+    fn words(rom: &[u8]) -> Vec<u16> {
+        rom.chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+            .collect()
+    }
+
+    #[test]
+    fn assembles_statements() {
+        let rom = assemble(
+            ": main
+                clear
+                v0 := 5 v1 := v0 v2 += 3 v2 -= 1 v3 := random 0xFF
+                v1 |= v2 v1 >>= v2 v1 =- v2
+                i := tile i += v1 i := hex v0
+                sprite v0 v1 5 delay := v0 v4 := key
+                save v3 load v0 - v2 bcd v4
+                jump main
+            : tile 0xF0 0b10010000 -1",
+        )
+        .unwrap();
+        assert_eq!(
+            words(&rom),
+            [
+                0x00E0, 0x6005, 0x8100, 0x7203, 0x72FF, 0xC3FF, 0x8121, 0x8126, 0x8127, 0xA226,
+                0xF11E, 0xF029, 0xD015, 0xF015, 0xF40A, 0xF355, 0x5023, 0xF433, 0x1200, 0xF090,
+                0xFF00,
+            ]
+        );
+    }
+
+    #[test]
+    fn reserves_a_jump_to_main_unless_it_comes_first() {
+        let rom = assemble(": helper return : main helper").unwrap();
+        assert_eq!(words(&rom), [0x1204, 0x00EE, 0x2202]);
+    }
+
+    #[test]
+    fn expands_constants_aliases_macros_and_calc() {
+        let rom = assemble(
+            ":const SPEED 2
+            :alias px v3
+            :calc LIMIT { SPEED * 8 + 4 }
+            :macro move reg amount { reg += amount }
+            : main
+                move px SPEED
+                :unpack 0xA later
+                px := LIMIT
+            : later
+            :org 0x300
+                :byte { main >> 8 }",
+        )
+        .unwrap();
+        assert_eq!(words(&rom[..10]), [0x1202, 0x7302, 0x60A2, 0x610A, 0x6318]);
+        assert_eq!(rom[0x100], 0x02);
+    }
+
+    #[test]
+    fn assembles_structured_control_flow() {
+        let rom = assemble(
+            ": main
+                if v0 == 1 then v1 := 2
+                if v0 != v1 begin
+                    v2 := 1
+                else
+                    v2 := 2
+                end
+                loop
+                    v0 += 1
+                    while v0 -key
+                again",
+        )
+        .unwrap();
+        assert_eq!(
+            words(&rom),
+            [
+                0x4001, 0x6102, 0x9010, 0x120C, 0x6201, 0x120E, 0x6202, 0x7001, 0xE0A1, 0x1216,
+                0x120E,
+            ]
+        );
+    }
+
+    #[test]
+    fn assembles_xo_chip_statements() {
+        let rom = assemble(": main i := long data plane 3 audio save v1 - v4 : data 0xAA").unwrap();
+        assert_eq!(
+            words(&rom),
+            [0xF000, 0x020A, 0xF301, 0xF002, 0x5142, 0xAA00]
+        );
+    }
+
+    #[test]
+    fn reports_errors_with_line_and_column() {
+        let error = assemble(": main\n  v0 := 5\n  v1 := 300").unwrap_err();
+        assert_eq!((error.line, error.column), (3, 9));
+
+        let error = assemble(": main\n    jump nowhere").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 10));
+        assert_eq!(error.message, "undefined label 'nowhere'");
+
+        let error = assemble(": main\n loop v0 += 1").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 2));
+
+        assert_eq!(
+            assemble("v0 := 1").unwrap_err().message,
+            "missing ': main' label"
+        );
+    }
+}
@@ -0,0 +1,174 @@
+// `:calc` expressions: all binary operators share one precedence and
+// evaluate right to left, parentheses group
+use crate::{error::AssembleError, lexer::Token};
+
+pub fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+pub fn evaluate<F>(tokens: &[Token], end: &Token, lookup: F) -> Result<f64, AssembleError>
+where
+    F: Fn(&Token) -> Result<f64, AssembleError>,
+{
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        end,
+        lookup,
+    };
+    let value = parser.expression()?;
+    match parser.tokens.get(parser.position) {
+        Some(token) => Err(AssembleError::at(
+            token,
+            format!("unexpected '{}'", token.text),
+        )),
+        None => Ok(value),
+    }
+}
+
+struct Parser<'a, F> {
+    tokens: &'a [Token],
+    position: usize,
+    end: &'a Token,
+    lookup: F,
+}
+
+impl<F> Parser<'_, F>
+where
+    F: Fn(&Token) -> Result<f64, AssembleError>,
+{
+    fn next(&mut self) -> Result<&Token, AssembleError> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .ok_or_else(|| AssembleError::at(self.end, "incomplete expression"))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn expression(&mut self) -> Result<f64, AssembleError> {
+        let left = self.term()?;
+        let Some(token) = self.tokens.get(self.position) else {
+            return Ok(left);
+        };
+        if token.text == ")" {
+            return Ok(left);
+        }
+        let operator = token.clone();
+        self.position += 1;
+        let right = self.expression()?;
+        binary(&operator, left, right)
+    }
+
+    fn term(&mut self) -> Result<f64, AssembleError> {
+        let token = self.next()?.clone();
+        match token.text.as_str() {
+            "(" => {
+                let value = self.expression()?;
+                match self.next()? {
+                    close if close.text == ")" => Ok(value),
+                    other => Err(AssembleError::at(other, "expected ')'")),
+                }
+            }
+            "-" => Ok(-self.term()?),
+            "~" => Ok(!(self.term()? as i64) as f64),
+            "!" => Ok((self.term()? == 0.0) as u8 as f64),
+            "abs" => Ok(self.term()?.abs()),
+            "floor" => Ok(self.term()?.floor()),
+            "ceil" => Ok(self.term()?.ceil()),
+            "sqrt" => Ok(self.term()?.sqrt()),
+            "PI" => Ok(core::f64::consts::PI),
+            "E" => Ok(core::f64::consts::E),
+            text => match parse_number(text) {
+                Some(value) => Ok(value as f64),
+                None => (self.lookup)(&token),
+            },
+        }
+    }
+}
+
+fn binary(operator: &Token, left: f64, right: f64) -> Result<f64, AssembleError> {
+    let (a, b) = (left as i64, right as i64);
+    Ok(match operator.text.as_str() {
+        "+" => left + right,
+        "-" => left - right,
+        "*" => left * right,
+        "/" if right == 0.0 => return Err(AssembleError::at(operator, "division by zero")),
+        "/" => left / right,
+        "%" if b == 0 => return Err(AssembleError::at(operator, "division by zero")),
+        "%" => (a % b) as f64,
+        "&" => (a & b) as f64,
+        "|" => (a | b) as f64,
+        "^" => (a ^ b) as f64,
+        "<<" => (a << (b & 63)) as f64,
+        ">>" => (a >> (b & 63)) as f64,
+        "pow" => left.powf(right),
+        "min" => left.min(right),
+        "max" => left.max(right),
+        "<" => (left < right) as u8 as f64,
+        ">" => (left > right) as u8 as f64,
+        "<=" => (left <= right) as u8 as f64,
+        ">=" => (left >= right) as u8 as f64,
+        "==" => (left == right) as u8 as f64,
+        "!=" => (left != right) as u8 as f64,
+        text => {
+            return Err(AssembleError::at(
+                operator,
+                format!("unknown operator '{text}'"),
+            ));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{evaluate, parse_number};
+    use crate::{error::AssembleError, lexer::tokenize};
+
+    fn calc(source: &str) -> Result<f64, AssembleError> {
+        let tokens = tokenize(source);
+        let end = tokens.last().unwrap().clone();
+        evaluate(&tokens, &end, |token| match token.text.as_str() {
+            "WIDTH" => Ok(64.0),
+            _ => Err(AssembleError::at(token, "undefined")),
+        })
+    }
+
+    #[test]
+    fn parses_octo_numbers() {
+        assert_eq!(parse_number("42"), Some(42));
+        assert_eq!(parse_number("-7"), Some(-7));
+        assert_eq!(parse_number("0xFF"), Some(255));
+        assert_eq!(parse_number("0b1010"), Some(10));
+        assert_eq!(parse_number("v0"), None);
+    }
+
+    #[test]
+    fn evaluates_right_to_left_with_equal_precedence() {
+        assert_eq!(calc("2 * 3 + 4"), Ok(14.0));
+        assert_eq!(calc("( 2 * 3 ) + 4"), Ok(10.0));
+        assert_eq!(calc("WIDTH - 4 / 2"), Ok(62.0));
+        assert_eq!(calc("- 1 & 0xFF"), Ok(255.0));
+        assert_eq!(calc("1 << 4 max 2"), Ok(16.0));
+    }
+
+    #[test]
+    fn reports_errors_at_the_offending_token() {
+        let error = calc("1 +\n  HEIGHT").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 3));
+        assert_eq!(calc("1 / 0").unwrap_err().column, 3);
+    }
+}
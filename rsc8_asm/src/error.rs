@@ -0,0 +1,32 @@
+use crate::lexer::Token;
+
+#[derive(PartialEq, Eq)]
+pub struct AssembleError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl AssembleError {
+    pub(crate) fn at(token: &Token, message: impl Into<String>) -> Self {
+        Self {
+            line: token.line,
+            column: token.column,
+            message: message.into(),
+        }
+    }
+}
+
+impl core::fmt::Debug for AssembleError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl core::fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl core::error::Error for AssembleError {}
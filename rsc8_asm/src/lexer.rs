@@ -0,0 +1,63 @@
+// Octo tokens are separated by whitespace, `#` starts a comment running to the end of the line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub text: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+pub fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (line_index, line) in source.lines().enumerate() {
+        let mut chars = line.char_indices().peekable();
+        while let Some(&(start, c)) = chars.peek() {
+            if c == '#' {
+                break;
+            }
+            if c.is_whitespace() {
+                chars.next();
+                continue;
+            }
+            let mut end = start;
+            while let Some(&(index, c)) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                end = index + c.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token {
+                text: line[start..end].to_string(),
+                line: line_index + 1,
+                column: line[..start].chars().count() + 1,
+            });
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tokenize;
+
+    #[test]
+    fn tokenizes_with_positions_and_skips_comments() {
+        let tokens = tokenize(": main\n  v0 := 5 # set v0\n\t# only a comment\nloop again");
+        let summary: Vec<_> = tokens
+            .iter()
+            .map(|token| (token.text.as_str(), token.line, token.column))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (":", 1, 1),
+                ("main", 1, 3),
+                ("v0", 2, 3),
+                (":=", 2, 6),
+                ("5", 2, 9),
+                ("loop", 4, 1),
+                ("again", 4, 6),
+            ]
+        );
+    }
+}
@@ -0,0 +1,6 @@
+mod assembler;
+mod calc;
+pub mod error;
+mod lexer;
+
+pub use assembler::assemble;
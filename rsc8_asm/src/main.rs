@@ -0,0 +1,17 @@
+use rsc8_asm::assemble;
+use std::{env, error::Error, fs};
+
+const USAGE: &str = "Usage: rsc8_asm <source.8o> [output.ch8]";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let source_path = args.next().ok_or(USAGE)?;
+    let output_path = args
+        .next()
+        .unwrap_or_else(|| source_path.trim_end_matches(".8o").to_string() + ".ch8");
+
+    let source = fs::read_to_string(&source_path)?;
+    let rom = assemble(&source).map_err(|error| format!("{source_path}:{error}"))?;
+    fs::write(&output_path, rom)?;
+    Ok(())
+}
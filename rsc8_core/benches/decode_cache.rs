@@ -0,0 +1,67 @@
+// Compares Chip8::tick with and without the decode cache on tight loops of
+// register and memory instructions, and of sprite drawing. Run with
+// `cargo bench -p rsc8_core --features alloc`.
+use rsc8_core::{chip8::Chip8, quirks::Quirks, rng::FixedSequence};
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+const TICKS: usize = 10_000_000;
+
+#[rustfmt::skip]
+const ALU: [u8; 24] = [
+    0x60, 0x01, // 0x200 LD V0, 0x01
+    0x71, 0x03, // 0x202 ADD V1, 0x03
+    0x82, 0x14, // 0x204 ADD V2, V1
+    0x83, 0x26, // 0x206 SHR V3, V2
+    0xA3, 0x00, // 0x208 LD I, 0x300
+    0xF3, 0x33, // 0x20A LD B, V3
+    0xF2, 0x65, // 0x20C LD V2, [I]
+    0x34, 0x00, // 0x20E SE V4, 0x00
+    0x74, 0xFF, // 0x210 ADD V4, 0xFF
+    0x75, 0x01, // 0x212 ADD V5, 0x01
+    0x86, 0x53, // 0x214 XOR V6, V5
+    0x12, 0x00, // 0x216 JP 0x200
+];
+
+#[rustfmt::skip]
+const SPRITES: [u8; 12] = [
+    0xA0, 0x00, // 0x200 LD I, 0x000
+    0x70, 0x01, // 0x202 ADD V0, 0x01
+    0xD0, 0x15, // 0x204 DRW V0, V1, 5
+    0xD0, 0x15, // 0x206 DRW V0, V1, 5
+    0x00, 0xE0, // 0x208 CLS
+    0x12, 0x00, // 0x20A JP 0x200
+];
+
+fn run(rom: &[u8], cached: bool) -> Duration {
+    // No display wait, every tick runs an instruction
+    let mut chip8 = Chip8::with_quirks(FixedSequence::new(&[0]), Quirks::CHIP48);
+    chip8.load_rom(rom).unwrap();
+    if cached {
+        chip8.enable_decode_cache();
+    }
+    let start = Instant::now();
+    for _ in 0..TICKS {
+        chip8.tick().unwrap();
+    }
+    black_box(&chip8.register_v);
+    start.elapsed()
+}
+
+fn main() {
+    for (program, rom) in [("alu", &ALU[..]), ("sprites", &SPRITES[..])] {
+        // Warm up both paths before measuring
+        run(rom, false);
+        run(rom, true);
+        for (path, cached) in [("decode every tick", false), ("decode cache", true)] {
+            let elapsed = run(rom, cached);
+            println!(
+                "{program:<8} {path:<18} {:>6.2} ns/tick {:>7.1} Mticks/s",
+                elapsed.as_nanos() as f64 / TICKS as f64,
+                TICKS as f64 / elapsed.as_secs_f64() / 1e6
+            );
+        }
+    }
+}
@@ -0,0 +1,562 @@
+// Recursive traversal from the program start. Unlike the linear sweep in
+// disassembler, only bytes some path of jumps, calls, skips and fallthroughs
+// reaches are code, so sprite data between routines is not decoded as
+// instructions.
+use crate::{
+    chip8::XO_MEMORY_SIZE,
+    disassembler::{Disassembly, LabelKind, Line, LineKind, Syntax, decode_at},
+    error::InstructionError,
+    instruction::{Instruction, LONG_OPCODE_PREFIX},
+};
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec,
+    vec::Vec,
+};
+use core::{
+    fmt::{self, Write},
+    ops::Range,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    Fallthrough,
+    Jump,
+    Skip,
+    Call,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub kind: EdgeKind,
+    pub target: u16,
+}
+
+// Why a block has no successor inside the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockExit {
+    Return,
+    Exit,
+    // BNNN, the target depends on V0 at run time
+    IndirectJump,
+    // The next instruction does not decode or lies past the end of the ROM
+    Invalid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: u16,
+    // Exclusive, 0x10000 for a block ending at the top of memory
+    pub end: u32,
+    pub edges: Vec<Edge>,
+    pub exit: Option<BlockExit>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteKind {
+    Code,
+    // Referenced by ANNN or F000 NNNN, up to the next code
+    Data,
+    // Neither reached nor referenced. Dead code, or data only reached
+    // through computed addresses.
+    Unreachable,
+}
+
+pub struct Analysis {
+    rom: Vec<u8>,
+    program_start: u16,
+    kinds: Vec<ByteKind>,
+    instructions: BTreeMap<u16, Instruction>,
+    pub blocks: BTreeMap<u16, BasicBlock>,
+    // The program start and every call target, with the subroutines each calls
+    pub subroutines: BTreeMap<u16, BTreeSet<u16>>,
+    pub labels: BTreeMap<u16, LabelKind>,
+    // Addresses of BNNN instructions, code they jump to may be missing
+    pub indirect_jumps: BTreeSet<u16>,
+}
+
+// Where execution continues after the instruction at address, and whether
+// the instruction ends its basic block
+fn flow(
+    rom: &[u8],
+    program_start: u16,
+    address: u16,
+    instruction: &Instruction,
+) -> (Vec<Edge>, Option<BlockExit>, bool) {
+    let edge = |kind, target| Edge { kind, target };
+    let next = address.wrapping_add(instruction.size());
+    match *instruction {
+        Instruction::Ins1NNN(nnn) => (vec![edge(EdgeKind::Jump, nnn)], None, true),
+        Instruction::Ins2NNN(nnn) => (
+            vec![edge(EdgeKind::Call, nnn), edge(EdgeKind::Fallthrough, next)],
+            None,
+            true,
+        ),
+        Instruction::Ins00EE => (Vec::new(), Some(BlockExit::Return), true),
+        Instruction::Ins00FD => (Vec::new(), Some(BlockExit::Exit), true),
+        Instruction::InsBNNN(_) => (Vec::new(), Some(BlockExit::IndirectJump), true),
+        Instruction::Ins3XNN(..)
+        | Instruction::Ins4XNN(..)
+        | Instruction::Ins5XY0(..)
+        | Instruction::Ins9XY0(..)
+        | Instruction::InsEX9E(_)
+        | Instruction::InsEXA1(_) => {
+            // F000 NNNN is skipped whole
+            let next_is_long = (next as usize)
+                .checked_sub(program_start as usize)
+                .and_then(|offset| rom.get(offset..offset + 2))
+                == Some(&LONG_OPCODE_PREFIX.to_be_bytes());
+            let skip = next.wrapping_add(if next_is_long { 4 } else { 2 });
+            (
+                vec![
+                    edge(EdgeKind::Fallthrough, next),
+                    edge(EdgeKind::Skip, skip),
+                ],
+                None,
+                true,
+            )
+        }
+        _ => (vec![edge(EdgeKind::Fallthrough, next)], None, false),
+    }
+}
+
+// rom is loaded at program_start, MachineConfig::program_start
+pub fn analyze(rom: &[u8], program_start: u16) -> Result<Analysis, InstructionError> {
+    let base = program_start as usize;
+    if base + rom.len() > XO_MEMORY_SIZE {
+        return Err(InstructionError::RomTooLarge {
+            rom_size: rom.len(),
+            max_size: XO_MEMORY_SIZE - base,
+        });
+    }
+    let in_rom = |address: u16| (base..base + rom.len()).contains(&(address as usize));
+
+    let mut instructions = BTreeMap::new();
+    let mut leaders = BTreeSet::from([program_start]);
+    let mut calls = BTreeSet::new();
+    let mut jumps = BTreeSet::new();
+    let mut data = BTreeSet::new();
+    let mut indirect_jumps = BTreeSet::new();
+    let mut pending = vec![program_start];
+    while let Some(address) = pending.pop() {
+        if !in_rom(address) || instructions.contains_key(&address) {
+            continue;
+        }
+        let Some(instruction) = decode_at(rom, address as usize - base) else {
+            continue;
+        };
+        let (edges, _, ends_block) = flow(rom, program_start, address, &instruction);
+        for edge in &edges {
+            if ends_block {
+                leaders.insert(edge.target);
+            }
+            match edge.kind {
+                EdgeKind::Call => {
+                    calls.insert(edge.target);
+                }
+                EdgeKind::Jump => {
+                    jumps.insert(edge.target);
+                }
+                _ => {}
+            }
+            pending.push(edge.target);
+        }
+        match instruction {
+            Instruction::InsANNN(nnn) | Instruction::InsF000(nnn) if in_rom(nnn) => {
+                data.insert(nnn);
+            }
+            Instruction::InsBNNN(_) => {
+                indirect_jumps.insert(address);
+            }
+            _ => {}
+        }
+        instructions.insert(address, instruction);
+    }
+
+    let mut blocks = BTreeMap::new();
+    for &start in leaders
+        .iter()
+        .filter(|start| instructions.contains_key(start))
+    {
+        let mut address = start;
+        let (edges, exit) = loop {
+            let (edges, exit, ends_block) =
+                flow(rom, program_start, address, &instructions[&address]);
+            if ends_block {
+                break (edges, exit);
+            }
+            let next = edges[0].target;
+            if !instructions.contains_key(&next) {
+                break (Vec::new(), Some(BlockExit::Invalid));
+            }
+            if leaders.contains(&next) {
+                break (edges, None);
+            }
+            address = next;
+        };
+        let end = address as u32 + instructions[&address].size() as u32;
+        blocks.insert(
+            start,
+            BasicBlock {
+                start,
+                end,
+                edges,
+                exit,
+            },
+        );
+    }
+
+    // Everything a subroutine reaches without following calls belongs to it
+    let mut subroutines = BTreeMap::new();
+    for entry in calls.iter().copied().chain([program_start]) {
+        if !blocks.contains_key(&entry) {
+            continue;
+        }
+        let mut callees = BTreeSet::new();
+        let mut visited = BTreeSet::new();
+        let mut pending = vec![entry];
+        while let Some(start) = pending.pop() {
+            let Some(block) = blocks.get(&start).filter(|_| visited.insert(start)) else {
+                continue;
+            };
+            for edge in &block.edges {
+                if edge.kind == EdgeKind::Call {
+                    callees.insert(edge.target);
+                } else {
+                    pending.push(edge.target);
+                }
+            }
+        }
+        subroutines.insert(entry, callees);
+    }
+
+    let mut kinds = vec![ByteKind::Unreachable; rom.len()];
+    for (&address, instruction) in &instructions {
+        let offset = address as usize - base;
+        let end = (offset + instruction.size() as usize).min(rom.len());
+        kinds[offset..end].fill(ByteKind::Code);
+    }
+    for &address in &data {
+        for kind in &mut kinds[address as usize - base..] {
+            if *kind == ByteKind::Code {
+                break;
+            }
+            *kind = ByteKind::Data;
+        }
+    }
+
+    let mut labels = BTreeMap::new();
+    for &address in &data {
+        if kinds[address as usize - base] == ByteKind::Data {
+            labels.insert(address, LabelKind::Data);
+        }
+    }
+    for &address in jumps.iter().filter(|address| blocks.contains_key(address)) {
+        labels.insert(address, LabelKind::Jump);
+    }
+    for &address in subroutines
+        .keys()
+        .filter(|&&address| address != program_start)
+    {
+        labels.insert(address, LabelKind::Subroutine);
+    }
+
+    Ok(Analysis {
+        rom: rom.to_vec(),
+        program_start,
+        kinds,
+        instructions,
+        blocks,
+        subroutines,
+        labels,
+        indirect_jumps,
+    })
+}
+
+impl Analysis {
+    pub fn kind_at(&self, address: u16) -> Option<ByteKind> {
+        let offset = (address as usize).checked_sub(self.program_start as usize)?;
+        self.kinds.get(offset).copied()
+    }
+
+    // Exclusive ends, so a range reaching the top of memory ends at 0x10000
+    pub fn unreachable(&self) -> Vec<Range<u32>> {
+        let mut ranges: Vec<Range<u32>> = Vec::new();
+        for (offset, kind) in self.kinds.iter().enumerate() {
+            if *kind != ByteKind::Unreachable {
+                continue;
+            }
+            let address = self.program_start as u32 + offset as u32;
+            match ranges.last_mut() {
+                Some(range) if range.end == address => range.end += 1,
+                _ => ranges.push(address..address + 1),
+            }
+        }
+        ranges
+    }
+
+    // Reached code as instructions and referenced data bytewise. Unreachable
+    // bytes are swept linearly and listed as code where they decode.
+    pub fn disassemble(&self, syntax: Syntax) -> Disassembly {
+        let rom = &self.rom;
+        let mut lines = Vec::new();
+        let mut offset = 0;
+        while offset < rom.len() {
+            // analyze keeps the ROM below 0x10000
+            let address = (self.program_start as usize + offset) as u16;
+            let unreachable =
+                |offset: usize| self.kinds.get(offset) == Some(&ByteKind::Unreachable);
+            let (kind, size) = match (self.kinds[offset], self.instructions.get(&address)) {
+                (ByteKind::Code, Some(instruction)) => {
+                    (LineKind::Code(*instruction), instruction.size() as usize)
+                }
+                (ByteKind::Unreachable, _) => match decode_at(rom, offset) {
+                    Some(instruction)
+                        if (offset..offset + instruction.size() as usize).all(unreachable) =>
+                    {
+                        (LineKind::Code(instruction), instruction.size() as usize)
+                    }
+                    _ if unreachable(offset + 1) => (LineKind::Data, 2),
+                    _ => (LineKind::Data, 1),
+                },
+                // Data, or the middle of an instruction overlapping another
+                _ => (LineKind::Data, 1),
+            };
+            let mut bytes = [0; 4];
+            bytes[..size].copy_from_slice(&rom[offset..offset + size]);
+            lines.push(Line {
+                address,
+                bytes,
+                size,
+                kind,
+            });
+            offset += size;
+        }
+
+        Disassembly {
+            lines,
+            labels: self.labels.clone(),
+            syntax,
+        }
+    }
+
+    // Marks unreachable lines and indirect jumps in a listing
+    pub fn annotate<'a>(&'a self, disassembly: &'a Disassembly) -> Annotated<'a> {
+        Annotated {
+            analysis: self,
+            disassembly,
+        }
+    }
+
+    // Basic blocks with their instructions, calls dashed. Blocks ending in
+    // BNNN or an invalid instruction are red, targets outside the ROM or
+    // without code are plain text.
+    pub fn write_dot<W: Write>(&self, out: &mut W) -> fmt::Result {
+        writeln!(out, "digraph cfg {{")?;
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];")?;
+        for block in self.blocks.values() {
+            write!(out, "    \"0x{:03X}\" [label=\"", block.start)?;
+            for (&address, instruction) in self
+                .instructions
+                .range(block.start..)
+                .take_while(|&(&address, _)| (address as u32) < block.end)
+            {
+                write!(out, "0x{address:03X}  {instruction}\\l")?;
+            }
+            match block.exit {
+                Some(BlockExit::IndirectJump | BlockExit::Invalid) => {
+                    writeln!(out, "\", color=red];")?
+                }
+                _ => writeln!(out, "\"];")?,
+            }
+        }
+        let mut missing = BTreeSet::new();
+        for block in self.blocks.values() {
+            for edge in &block.edges {
+                write!(
+                    out,
+                    "    \"0x{:03X}\" -> \"0x{:03X}\"",
+                    block.start, edge.target
+                )?;
+                match edge.kind {
+                    EdgeKind::Fallthrough => writeln!(out, ";")?,
+                    EdgeKind::Jump => writeln!(out, " [label=\"jump\"];")?,
+                    EdgeKind::Skip => writeln!(out, " [label=\"skip\"];")?,
+                    EdgeKind::Call => writeln!(out, " [label=\"call\", style=dashed];")?,
+                }
+                if !self.blocks.contains_key(&edge.target) {
+                    missing.insert(edge.target);
+                }
+            }
+        }
+        for address in missing {
+            writeln!(out, "    \"0x{address:03X}\" [shape=plaintext];")?;
+        }
+        writeln!(out, "}}")
+    }
+
+    pub fn write_call_graph_dot<W: Write>(&self, out: &mut W) -> fmt::Result {
+        writeln!(out, "digraph calls {{")?;
+        for (entry, callees) in &self.subroutines {
+            writeln!(out, "    \"0x{entry:03X}\";")?;
+            for callee in callees {
+                writeln!(out, "    \"0x{entry:03X}\" -> \"0x{callee:03X}\";")?;
+            }
+        }
+        writeln!(out, "}}")
+    }
+}
+
+pub struct Annotated<'a> {
+    analysis: &'a Analysis,
+    disassembly: &'a Disassembly,
+}
+
+impl fmt::Display for Annotated<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.disassembly.write_annotated(f, |line| {
+            if self.analysis.indirect_jumps.contains(&line.address) {
+                Some("indirect jump, targets unknown")
+            } else if self.analysis.kind_at(line.address) == Some(ByteKind::Unreachable) {
+                Some("unreachable")
+            } else {
+                None
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::PROGRAM_START;
+    use alloc::string::{String, ToString};
+
+    // 0x200: A214, 2210, 3000, 120C, 1208, 00E0 (dead), B300, 00E0 (dead)
+    // 0x210: D015, 00EE, 0x214: F0 90 F0 90 F0 (sprite)
+    const ROM: [u8; 25] = [
+        0xA2, 0x14, 0x22, 0x10, 0x30, 0x00, 0x12, 0x0C, 0x12, 0x08, 0x00, 0xE0, 0xB3, 0x00, 0x00,
+        0xE0, 0xD0, 0x15, 0x00, 0xEE, 0xF0, 0x90, 0xF0, 0x90, 0xF0,
+    ];
+
+    #[test]
+    fn builds_blocks_and_call_graph() {
+        let analysis = analyze(&ROM, PROGRAM_START).unwrap();
+        assert_eq!(
+            analysis.blocks.keys().copied().collect::<Vec<_>>(),
+            [0x200, 0x204, 0x206, 0x208, 0x20C, 0x210]
+        );
+        assert_eq!(
+            analysis.blocks[&0x200].edges,
+            [
+                Edge {
+                    kind: EdgeKind::Call,
+                    target: 0x210
+                },
+                Edge {
+                    kind: EdgeKind::Fallthrough,
+                    target: 0x204
+                },
+            ]
+        );
+        assert_eq!(
+            analysis.blocks[&0x204].edges[1],
+            Edge {
+                kind: EdgeKind::Skip,
+                target: 0x208
+            }
+        );
+        assert_eq!(analysis.blocks[&0x20C].exit, Some(BlockExit::IndirectJump));
+        assert_eq!(analysis.blocks[&0x210].exit, Some(BlockExit::Return));
+        assert_eq!(analysis.indirect_jumps, BTreeSet::from([0x20C]));
+        assert_eq!(
+            analysis.subroutines,
+            BTreeMap::from([(0x200, BTreeSet::from([0x210])), (0x210, BTreeSet::new())])
+        );
+    }
+
+    #[test]
+    fn separates_code_data_and_unreachable_bytes() {
+        let analysis = analyze(&ROM, PROGRAM_START).unwrap();
+        assert_eq!(analysis.unreachable(), [0x20A..0x20C, 0x20E..0x210]);
+        assert_eq!(analysis.kind_at(0x210), Some(ByteKind::Code));
+        assert_eq!(analysis.kind_at(0x218), Some(ByteKind::Data));
+        assert_eq!(analysis.kind_at(0x219), None);
+        assert_eq!(analysis.labels.get(&0x214), Some(&LabelKind::Data));
+        assert_eq!(analysis.labels.get(&0x210), Some(&LabelKind::Subroutine));
+
+        let disassembly = analysis.disassemble(Syntax::Standard);
+        let listing = analysis.annotate(&disassembly).to_string();
+        assert!(listing.contains("    0x20A  00E0      CLS  ; unreachable\n"));
+        assert!(
+            listing
+                .contains("    0x20C  B300      JP V0, 0x300  ; indirect jump, targets unknown\n")
+        );
+        assert!(listing.contains("data_214:\n    0x214  F0        DB 0xF0\n"));
+    }
+
+    #[test]
+    fn exports_dot() {
+        let analysis = analyze(&ROM, PROGRAM_START).unwrap();
+        let mut dot = String::new();
+        analysis.write_dot(&mut dot).unwrap();
+        assert!(dot.starts_with("digraph cfg {\n"));
+        assert!(dot.contains("    \"0x210\" [label=\"0x210  DRW V0, V1, 5\\l0x212  RET\\l\"];\n"));
+        assert!(dot.contains("    \"0x20C\" [label=\"0x20C  JP V0, 0x300\\l\", color=red];\n"));
+        assert!(dot.contains("    \"0x200\" -> \"0x210\" [label=\"call\", style=dashed];\n"));
+        assert!(dot.contains("    \"0x204\" -> \"0x208\" [label=\"skip\"];\n"));
+
+        let mut calls = String::new();
+        analysis.write_call_graph_dot(&mut calls).unwrap();
+        assert!(calls.contains("    \"0x200\" -> \"0x210\";\n"));
+    }
+
+    #[test]
+    fn follows_program_start() {
+        // 0x600: A608, 2606, 1604, 00EE, 0x608: FF
+        let rom = [0xA6, 0x08, 0x26, 0x06, 0x16, 0x04, 0x00, 0xEE, 0xFF];
+        let analysis = analyze(&rom, 0x600).unwrap();
+        assert_eq!(
+            analysis.blocks.keys().copied().collect::<Vec<_>>(),
+            [0x600, 0x604, 0x606]
+        );
+        assert_eq!(
+            analysis.subroutines,
+            BTreeMap::from([(0x600, BTreeSet::from([0x606])), (0x606, BTreeSet::new())])
+        );
+        assert_eq!(analysis.kind_at(0x608), Some(ByteKind::Data));
+        assert_eq!(analysis.kind_at(0x200), None);
+        assert!(analysis.unreachable().is_empty());
+
+        let listing = analysis.disassemble(Syntax::Standard).to_string();
+        assert!(listing.contains("    0x600  A608      LD I, data_608\n"));
+        assert!(listing.contains("sub_606:\n    0x606  00EE      RET\n"));
+    }
+
+    #[test]
+    fn handles_roms_ending_at_the_top_of_memory() {
+        // 0xFFFA: 00E0, 00FD, 0xFFFE: AB CD
+        let analysis = analyze(&[0x00, 0xE0, 0x00, 0xFD, 0xAB, 0xCD], 0xFFFA).unwrap();
+        let unreachable = analysis.unreachable();
+        assert_eq!(unreachable.len(), 1);
+        assert_eq!(unreachable[0], 0xFFFE..0x10000);
+        let listing = analysis.disassemble(Syntax::Standard).to_string();
+        assert!(listing.contains("    0xFFFE  ABCD      LD I, 0xBCD\n"));
+
+        // 0xFFFC: 00E0, 00E0
+        let analysis = analyze(&[0x00, 0xE0, 0x00, 0xE0], 0xFFFC).unwrap();
+        assert_eq!(analysis.blocks[&0xFFFC].end, 0x10000);
+        assert_eq!(analysis.blocks[&0xFFFC].exit, Some(BlockExit::Invalid));
+        let mut dot = String::new();
+        analysis.write_dot(&mut dot).unwrap();
+        assert!(dot.contains("0xFFFE  CLS"));
+
+        assert_eq!(
+            analyze(&[0; 3], 0xFFFE).err(),
+            Some(InstructionError::RomTooLarge {
+                rom_size: 3,
+                max_size: 2
+            })
+        );
+    }
+}
@@ -1,40 +1,49 @@
-use crate::{error::InstructionError, instruction::Instruction};
+#[cfg(feature = "alloc")]
+use crate::decode_cache::DecodeCache;
+use crate::{
+    display::{self, Display},
+    error::{ConfigError, ExecutionError, InstructionError},
+    fontset::Fontset,
+    instruction::{Instruction, LONG_OPCODE_PREFIX},
+    machine::MachineConfig,
+    quirks::Quirks,
+    rng::RandomSource,
+};
 
 pub const MEMORY_SIZE: usize = 4096;
+pub const XO_MEMORY_SIZE: usize = 65536;
 pub const NUM_REGISTERS: usize = 16;
 pub const STACK_SIZE: usize = 16;
 pub const KEYPAD_SIZE: usize = 16;
-pub const SCREEN_WIDTH: usize = 64;
-pub const SCREEN_HEIGHT: usize = 32;
+pub const SCREEN_WIDTH: usize = 128;
+pub const SCREEN_HEIGHT: usize = 64;
+pub const LORES_SCREEN_WIDTH: usize = 64;
+pub const LORES_SCREEN_HEIGHT: usize = 32;
+pub const RPL_FLAGS_SIZE: usize = 16;
+pub const PLANE_COUNT: usize = 2;
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+pub const DEFAULT_PITCH: u8 = 64;
 pub const PROGRAM_START: u16 = 0x200;
-pub const ROM_START: usize = 512;
 pub const FONTSET_START: usize = 0;
 pub const FONTSET_SIZE: usize = 80;
+pub const BIG_FONTSET_SIZE: usize = 160;
 
-const FONTSET: [u8; FONTSET_SIZE] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-];
-
-pub struct Chip8<R>
+// FX0A's wait: first for a key that was not held when it started to be
+// pressed, then for that key to be released
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct KeyWait {
+    pub register: u8,
+    pub key: Option<usize>,
+}
+
+// CAPACITY bytes of memory are held inline, machine configs up to that size
+// can be used. The default fits every config, a CHIP-8 only build can use
+// Chip8<R, MEMORY_SIZE>.
+pub struct Chip8<R, const CAPACITY: usize = XO_MEMORY_SIZE>
 where
-    R: Iterator<Item = u16>,
+    R: RandomSource,
 {
-    pub memory: [u8; MEMORY_SIZE],
+    pub memory: [u8; CAPACITY],
     pub program_counter: u16,
     pub register_v: [u8; NUM_REGISTERS],
     pub register_i: u16,
@@ -43,19 +52,61 @@ where
     pub stack: [u16; STACK_SIZE],
     pub stack_pointer: u8,
     pub keypad: [bool; KEYPAD_SIZE],
-    pub screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    // Each pixel holds one bit per plane
+    pub screen: Display,
+    pub hires: bool,
+    pub selected_planes: u8,
     pub draw_flag: bool,
+    pub rpl_flags: [u8; RPL_FLAGS_SIZE],
+    pub exited: bool,
+    pub audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+    pub pitch: u8,
     pub rng: R,
-    pub wait_for_key_release: Option<usize>,
+    pub wait_for_vblank: bool,
+    pub quirks: Quirks,
+    pub(crate) config: MachineConfig,
+    pub(crate) key_wait: Option<KeyWait>,
+    #[cfg(feature = "alloc")]
+    decode_cache: Option<DecodeCache>,
 }
 
 impl<R> Chip8<R>
 where
-    R: Iterator<Item = u16>,
+    R: RandomSource,
 {
     pub fn new(rng: R) -> Self {
+        Self::with_quirks(rng, Quirks::default())
+    }
+
+    pub fn with_quirks(rng: R, quirks: Quirks) -> Self {
+        Self::build(rng, quirks)
+    }
+
+    pub fn with_config(rng: R, quirks: Quirks, config: MachineConfig) -> Result<Self, ConfigError> {
+        Self::with_capacity(rng, quirks, config)
+    }
+}
+
+impl<R, const CAPACITY: usize> Chip8<R, CAPACITY>
+where
+    R: RandomSource,
+{
+    // For a capacity other than the default, e.g.
+    // Chip8::<_, MEMORY_SIZE>::with_capacity(rng, quirks, MachineConfig::CHIP8)
+    pub fn with_capacity(
+        rng: R,
+        quirks: Quirks,
+        config: MachineConfig,
+    ) -> Result<Self, ConfigError> {
+        let mut chip8 = Self::build(rng, quirks);
+        chip8.set_config(config)?;
+        Ok(chip8)
+    }
+
+    // The CHIP-8 config, which set_config replaces when it does not fit
+    fn build(rng: R, quirks: Quirks) -> Self {
         Self {
-            memory: [0; MEMORY_SIZE],
+            memory: [0; CAPACITY],
             program_counter: PROGRAM_START,
             register_v: [0; NUM_REGISTERS],
             register_i: 0,
@@ -64,44 +115,274 @@ where
             stack: [0; STACK_SIZE],
             stack_pointer: 0,
             keypad: [false; KEYPAD_SIZE],
-            screen: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            screen: Display::new(),
+            hires: false,
+            selected_planes: 1,
             draw_flag: false,
+            rpl_flags: [0; RPL_FLAGS_SIZE],
+            exited: false,
+            audio_pattern: [0; AUDIO_PATTERN_SIZE],
+            pitch: DEFAULT_PITCH,
             rng,
-            wait_for_key_release: None,
+            wait_for_vblank: false,
+            quirks,
+            config: MachineConfig::CHIP8,
+            key_wait: None,
+            #[cfg(feature = "alloc")]
+            decode_cache: None,
+        }
+    }
+
+    pub fn config(&self) -> MachineConfig {
+        self.config
+    }
+
+    pub fn memory_size(&self) -> usize {
+        self.config.memory_size
+    }
+
+    // The memory of the machine config, without the unused capacity
+    pub fn memory(&self) -> &[u8] {
+        &self.memory[..self.config.memory_size]
+    }
+
+    // Writes through here bypass the decode cache, follow them with
+    // invalidate_decode_cache
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory[..self.config.memory_size]
+    }
+
+    // Changes the memory layout and resets the machine to start the program.
+    // Memory is kept, reload the fonts and the ROM if they moved.
+    pub fn set_config(&mut self, config: MachineConfig) -> Result<(), ConfigError> {
+        Self::check_config(&config)?;
+        self.config = config;
+        self.memory[config.memory_size..].fill(0);
+        self.invalidate_decode_cache();
+        self.reset();
+        Ok(())
+    }
+
+    pub(crate) fn check_config(config: &MachineConfig) -> Result<(), ConfigError> {
+        config.validate()?;
+        if config.memory_size > CAPACITY {
+            return Err(ConfigError::MemoryExceedsCapacity {
+                memory_size: config.memory_size,
+                capacity: CAPACITY,
+            });
         }
+        Ok(())
+    }
+
+    // Like the reset switch: everything but memory, the RPL flags, quirks
+    // and the random source goes back to power-on state
+    pub fn reset(&mut self) {
+        self.program_counter = self.config.program_start;
+        self.register_v = [0; NUM_REGISTERS];
+        self.register_i = 0;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.stack = [0; STACK_SIZE];
+        self.stack_pointer = 0;
+        self.keypad = [false; KEYPAD_SIZE];
+        self.screen = Display::new();
+        self.hires = false;
+        self.selected_planes = 1;
+        self.draw_flag = false;
+        self.exited = false;
+        self.audio_pattern = [0; AUDIO_PATTERN_SIZE];
+        self.pitch = DEFAULT_PITCH;
+        self.key_wait = None;
+        self.wait_for_vblank = false;
     }
 
     pub fn load_fontset(&mut self) {
-        self.memory[..FONTSET.len()].copy_from_slice(&FONTSET);
+        self.load_custom_fontset(&Fontset::default());
+    }
+
+    // Writes the fonts where the machine config puts them
+    pub fn load_custom_fontset(&mut self, fontset: &Fontset) {
+        let start = self.config.fontset_start;
+        self.memory[start..start + FONTSET_SIZE].copy_from_slice(&fontset.small);
+        let big_start = self.config.big_fontset_start();
+        self.memory[big_start..big_start + BIG_FONTSET_SIZE].copy_from_slice(&fontset.big);
+        self.invalidate_decode_cache();
+    }
+
+    pub fn screen_width(&self) -> usize {
+        if self.hires {
+            SCREEN_WIDTH
+        } else {
+            LORES_SCREEN_WIDTH
+        }
+    }
+
+    pub fn screen_height(&self) -> usize {
+        if self.hires {
+            SCREEN_HEIGHT
+        } else {
+            LORES_SCREEN_HEIGHT
+        }
     }
 
     pub fn load_rom(&mut self, buffer: &[u8]) -> Result<(), InstructionError> {
-        let max_size = MEMORY_SIZE - ROM_START;
+        let start = self.config.program_start as usize;
+        let max_size = self.config.program_end() - start;
         if buffer.len() > max_size {
             return Err(InstructionError::RomTooLarge {
                 rom_size: buffer.len(),
                 max_size,
             });
         }
-        let rom_end = ROM_START + buffer.len();
-        self.memory[ROM_START..rom_end].copy_from_slice(buffer);
+        self.memory[start..start + buffer.len()].copy_from_slice(buffer);
+        self.invalidate_decode_cache();
         Ok(())
     }
 
-    pub fn tick(&mut self) -> Result<(), InstructionError> {
+    pub fn tick(&mut self) -> Result<(), ExecutionError> {
+        if self.wait_for_vblank || self.exited || self.key_wait.is_some() {
+            return Ok(());
+        }
+        let program_counter = self.program_counter;
+        self.fetch_instruction()
+            .and_then(|instruction| self.execute_instruction(&instruction))
+            .map_err(|error| self.execution_error(program_counter, error))
+    }
+
+    // Attaches the state of the machine to an error raised by the
+    // instruction at program_counter
+    pub fn execution_error(&self, program_counter: u16, error: InstructionError) -> ExecutionError {
+        let pc = program_counter as usize;
+        let opcode = (pc + 1 < self.config.memory_size)
+            .then(|| u16::from_be_bytes([self.memory[pc], self.memory[pc + 1]]));
+        let depth = (self.stack_pointer as usize).min(STACK_SIZE);
+        ExecutionError::new(
+            error,
+            program_counter,
+            opcode,
+            self.instruction_at(program_counter).ok(),
+            self.register_i,
+            &self.stack[..depth],
+        )
+    }
+
+    // Decoded instructions are reused until memory under them is written.
+    // Writes through the memory field or memory_mut bypass the cache, follow
+    // them with invalidate_decode_cache.
+    #[cfg(feature = "alloc")]
+    pub fn enable_decode_cache(&mut self) {
+        if self.decode_cache.is_none() {
+            self.decode_cache = Some(DecodeCache::new(self.config.memory_size));
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn disable_decode_cache(&mut self) {
+        self.decode_cache = None;
+    }
+
+    pub fn invalidate_decode_cache(&mut self) {
+        #[cfg(feature = "alloc")]
+        if let Some(cache) = &mut self.decode_cache {
+            cache.reset(self.config.memory_size);
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    fn fetch_instruction(&mut self) -> Result<Instruction, InstructionError> {
+        let pc = self.program_counter;
+        // A cached instruction was fetched from here before, so the program
+        // counter cannot overflow
+        if let Some(instruction) = self.decode_cache.as_ref().and_then(|cache| cache.get(pc))
+            && pc as usize + instruction.size() as usize <= self.config.memory_size
+        {
+            self.program_counter += instruction.size();
+            return Ok(instruction);
+        }
+        let instruction = self.decode_next()?;
+        if let Some(cache) = &mut self.decode_cache {
+            cache.insert(pc, instruction);
+        }
+        Ok(instruction)
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn fetch_instruction(&mut self) -> Result<Instruction, InstructionError> {
+        self.decode_next()
+    }
+
+    fn decode_next(&mut self) -> Result<Instruction, InstructionError> {
         let opcode = self.fetch_opcode()?;
-        let instruction = Instruction::try_from(opcode)?;
-        self.execute_instruction(&instruction)
+        if opcode == LONG_OPCODE_PREFIX {
+            Instruction::decode(opcode, self.fetch_opcode()?)
+        } else {
+            Instruction::try_from(opcode)
+        }
     }
 
     pub fn tick_timer(&mut self) {
+        self.wait_for_vblank = false;
         self.delay_timer = self.delay_timer.saturating_sub(1);
         self.sound_timer = self.sound_timer.saturating_sub(1);
     }
 
+    pub fn keypad_bitmask(&self) -> u16 {
+        self.keypad
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (key, &pressed)| bits | ((pressed as u16) << key))
+    }
+
+    // Frontends report input through key_down, key_up and set_keys, which
+    // drive FX0A; writing the keypad field directly does not
+    pub fn key_down(&mut self, key: usize) {
+        if key >= KEYPAD_SIZE || self.keypad[key] {
+            return;
+        }
+        self.keypad[key] = true;
+        if let Some(wait) = &mut self.key_wait
+            && wait.key.is_none()
+        {
+            wait.key = Some(key);
+        }
+    }
+
+    pub fn key_up(&mut self, key: usize) {
+        if key >= KEYPAD_SIZE {
+            return;
+        }
+        self.keypad[key] = false;
+        if let Some(wait) = self.key_wait
+            && wait.key == Some(key)
+        {
+            self.register_v[wait.register as usize] = key as u8;
+            self.key_wait = None;
+        }
+    }
+
+    // Applies a whole keypad state at once, presses before releases. Of keys
+    // pressed together, FX0A takes the lowest.
+    pub fn set_keys(&mut self, bitmask: u16) {
+        for key in 0..KEYPAD_SIZE {
+            if bitmask & (1 << key) != 0 {
+                self.key_down(key);
+            }
+        }
+        for key in 0..KEYPAD_SIZE {
+            if bitmask & (1 << key) == 0 {
+                self.key_up(key);
+            }
+        }
+    }
+
+    // FX0A is waiting for a key press or release, ticks do nothing until then
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.key_wait.is_some()
+    }
+
     pub fn fetch_opcode(&mut self) -> Result<u16, InstructionError> {
         let pc = self.program_counter as usize;
-        if pc + 1 >= MEMORY_SIZE {
+        if pc + 1 >= self.config.memory_size {
             return Err(InstructionError::ProgramCounterOutOfBounds(
                 self.program_counter,
             ));
@@ -120,16 +401,44 @@ where
         Ok(((high_byte as u16) << 8) | low_byte as u16)
     }
 
+    // Decodes the instruction at the program counter without executing it
+    pub fn peek_instruction(&self) -> Result<Instruction, InstructionError> {
+        self.instruction_at(self.program_counter)
+    }
+
+    pub fn instruction_at(&self, address: u16) -> Result<Instruction, InstructionError> {
+        let opcode_at = |address: usize| {
+            if address + 1 >= self.config.memory_size {
+                return Err(InstructionError::ProgramCounterOutOfBounds(address as u16));
+            }
+            Ok(u16::from_be_bytes([
+                self.memory[address],
+                self.memory[address + 1],
+            ]))
+        };
+        let pc = address as usize;
+        let opcode = opcode_at(pc)?;
+        if opcode == LONG_OPCODE_PREFIX {
+            Instruction::decode(opcode, opcode_at(pc + 2)?)
+        } else {
+            Instruction::try_from(opcode)
+        }
+    }
+
     fn read_memory(&self, address: usize) -> Result<u8, InstructionError> {
-        self.memory
+        self.memory()
             .get(address)
             .copied()
             .ok_or(InstructionError::MemoryOutOfBounds(address))
     }
 
     fn write_memory(&mut self, address: usize, value: u8) -> Result<(), InstructionError> {
-        if let Some(cell) = self.memory.get_mut(address) {
+        if let Some(cell) = self.memory[..self.config.memory_size].get_mut(address) {
             *cell = value;
+            #[cfg(feature = "alloc")]
+            if let Some(cache) = &mut self.decode_cache {
+                cache.invalidate(address);
+            }
             Ok(())
         } else {
             Err(InstructionError::MemoryOutOfBounds(address))
@@ -137,18 +446,13 @@ where
     }
 
     fn skip_next_instruction(&mut self) -> Result<(), InstructionError> {
-        self.program_counter =
-            self.program_counter
-                .checked_add(2)
-                .ok_or(InstructionError::ProgramCounterOverflow(
-                    self.program_counter,
-                ))?;
-        Ok(())
-    }
-
-    fn repeat_current_instruction(&mut self) -> Result<(), InstructionError> {
-        self.program_counter = self.program_counter.checked_sub(2).ok_or(
-            InstructionError::ProgramCounterUnderflow(self.program_counter),
+        // F000 NNNN is four bytes long and has to be skipped as a whole
+        let pc = self.program_counter as usize;
+        let next_is_long = pc + 1 < self.config.memory_size
+            && ((self.memory[pc] as u16) << 8 | self.memory[pc + 1] as u16) == LONG_OPCODE_PREFIX;
+        let size = if next_is_long { 4 } else { 2 };
+        self.program_counter = self.program_counter.checked_add(size).ok_or(
+            InstructionError::ProgramCounterOverflow(self.program_counter),
         )?;
         Ok(())
     }
@@ -179,18 +483,109 @@ where
         Ok(self.keypad[key_index as usize])
     }
 
+    fn clear_planes(&mut self, planes: u8) {
+        self.screen.clear(planes);
+        self.draw_flag = true;
+    }
+
+    fn scroll_screen(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.screen_width(), self.screen_height());
+        self.screen
+            .scroll(self.selected_planes, width, height, dx, dy);
+        self.draw_flag = true;
+    }
+
+    fn draw_sprite(&mut self, x: u8, y: u8, n: u8) -> Result<(), InstructionError> {
+        let width = self.screen_width();
+        let height = self.screen_height();
+        // DXY0 draws a 16x16 sprite stored as two bytes per row
+        let (sprite_width, sprite_height) = if n == 0 { (16, 16) } else { (8, n as usize) };
+        let bytes_per_row = sprite_width / 8;
+        let vx = self.register_v[x as usize] as usize % width;
+        let vy = self.register_v[y as usize] as usize % height;
+        self.register_v[0xF] = 0;
+        let mut collision = false;
+        // Each selected plane consumes its own copy of the sprite data, in plane order
+        let mut sprite_address = self.register_i as usize;
+        for plane in 0..PLANE_COUNT {
+            if self.selected_planes & (1 << plane) == 0 {
+                continue;
+            }
+            for row in 0..sprite_height {
+                let mut screen_y = vy + row;
+                if screen_y >= height {
+                    if !self.quirks.sprite_wrap {
+                        break;
+                    }
+                    screen_y %= height;
+                }
+                let row_address = sprite_address + row * bytes_per_row;
+                let mut sprite_row = 0_u16;
+                for byte in 0..bytes_per_row {
+                    sprite_row = (sprite_row << 8) | self.read_memory(row_address + byte)? as u16;
+                }
+                let (visible, wrapped) = display::place_row(sprite_row, sprite_width, vx, width);
+                let bits = if self.quirks.sprite_wrap {
+                    visible | wrapped
+                } else {
+                    visible
+                };
+                collision |= self.screen.xor_row(plane, screen_y, bits);
+            }
+            sprite_address += sprite_height * bytes_per_row;
+        }
+        self.register_v[0xF] = collision as u8;
+        self.draw_flag = true;
+        Ok(())
+    }
+
+    fn register_range(x: u8, y: u8) -> impl Iterator<Item = usize> {
+        let (x, y) = (x as usize, y as usize);
+        let ascending = x <= y;
+        let (low, high) = if ascending { (x, y) } else { (y, x) };
+        (low..=high).map(move |offset| {
+            if ascending {
+                offset
+            } else {
+                high + low - offset
+            }
+        })
+    }
+
     pub fn execute_instruction(
         &mut self,
         instruction: &Instruction,
     ) -> Result<(), InstructionError> {
         match *instruction {
+            Instruction::Ins00CN(n) => {
+                self.scroll_screen(0, n as isize);
+            }
+            Instruction::Ins00DN(n) => {
+                self.scroll_screen(0, -(n as isize));
+            }
             Instruction::Ins00E0 => {
-                self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
-                self.draw_flag = true;
+                self.clear_planes(self.selected_planes);
             }
             Instruction::Ins00EE => {
                 self.program_counter = self.pop_stack()?;
             }
+            Instruction::Ins00FB => {
+                self.scroll_screen(4, 0);
+            }
+            Instruction::Ins00FC => {
+                self.scroll_screen(-4, 0);
+            }
+            Instruction::Ins00FD => {
+                self.exited = true;
+            }
+            Instruction::Ins00FE => {
+                self.hires = false;
+                self.clear_planes(u8::MAX);
+            }
+            Instruction::Ins00FF => {
+                self.hires = true;
+                self.clear_planes(u8::MAX);
+            }
             Instruction::Ins1NNN(nnn) => {
                 self.program_counter = nnn;
             }
@@ -213,6 +608,18 @@ where
                     self.skip_next_instruction()?;
                 }
             }
+            Instruction::Ins5XY2(x, y) => {
+                for (offset, index) in Self::register_range(x, y).enumerate() {
+                    let address = self.register_i as usize + offset;
+                    self.write_memory(address, self.register_v[index])?;
+                }
+            }
+            Instruction::Ins5XY3(x, y) => {
+                for (offset, index) in Self::register_range(x, y).enumerate() {
+                    let address = self.register_i as usize + offset;
+                    self.register_v[index] = self.read_memory(address)?;
+                }
+            }
             Instruction::Ins6XNN(x, nn) => {
                 self.register_v[x as usize] = nn;
             }
@@ -224,15 +631,21 @@ where
             }
             Instruction::Ins8XY1(x, y) => {
                 self.register_v[x as usize] |= self.register_v[y as usize];
-                self.register_v[0xF] = 0;
+                if self.quirks.vf_reset {
+                    self.register_v[0xF] = 0;
+                }
             }
             Instruction::Ins8XY2(x, y) => {
                 self.register_v[x as usize] &= self.register_v[y as usize];
-                self.register_v[0xF] = 0;
+                if self.quirks.vf_reset {
+                    self.register_v[0xF] = 0;
+                }
             }
             Instruction::Ins8XY3(x, y) => {
                 self.register_v[x as usize] ^= self.register_v[y as usize];
-                self.register_v[0xF] = 0;
+                if self.quirks.vf_reset {
+                    self.register_v[0xF] = 0;
+                }
             }
             Instruction::Ins8XY4(x, y) => {
                 let (result, carry) =
@@ -247,7 +660,9 @@ where
                 self.register_v[0xF] = !carry as u8;
             }
             Instruction::Ins8XY6(x, y) => {
-                self.register_v[x as usize] = self.register_v[y as usize];
+                if self.quirks.shift_uses_vy {
+                    self.register_v[x as usize] = self.register_v[y as usize];
+                }
                 let lsb = self.register_v[x as usize] & 1;
                 self.register_v[x as usize] >>= 1;
                 self.register_v[0xF] = lsb;
@@ -259,7 +674,9 @@ where
                 self.register_v[0xF] = !carry as u8;
             }
             Instruction::Ins8XYE(x, y) => {
-                self.register_v[x as usize] = self.register_v[y as usize];
+                if self.quirks.shift_uses_vy {
+                    self.register_v[x as usize] = self.register_v[y as usize];
+                }
                 let msb = self.register_v[x as usize] >> 7;
                 self.register_v[x as usize] <<= 1;
                 self.register_v[0xF] = msb;
@@ -273,39 +690,21 @@ where
                 self.register_i = nnn;
             }
             Instruction::InsBNNN(nnn) => {
-                self.program_counter = nnn + self.register_v[0] as u16;
+                let x = if self.quirks.jump_uses_vx {
+                    (nnn >> 8) as usize
+                } else {
+                    0
+                };
+                self.program_counter = nnn + self.register_v[x] as u16;
             }
             Instruction::InsCXNN(x, nn) => {
-                let random = self.rng.next().unwrap_or_default();
-                self.register_v[x as usize] = random as u8 & nn;
+                self.register_v[x as usize] = self.rng.next_byte() & nn;
             }
             Instruction::InsDXYN(x, y, n) => {
-                let vx = self.register_v[x as usize] % SCREEN_WIDTH as u8;
-                let vy = self.register_v[y as usize] % SCREEN_HEIGHT as u8;
-                self.register_v[0xF] = 0;
-                for row in 0..n {
-                    let screen_y = vy + row;
-                    if screen_y >= SCREEN_HEIGHT as u8 {
-                        break;
-                    }
-                    let sprite_address = self.register_i as usize + row as usize;
-                    let sprite_row = self.read_memory(sprite_address)?;
-                    for col in 0..8 {
-                        let screen_x = vx + col;
-                        if screen_x >= SCREEN_WIDTH as u8 {
-                            break;
-                        }
-                        let sprite_pixel = (sprite_row & (0b1000_0000 >> col)) != 0;
-                        let screen_pixel_index =
-                            screen_x as usize + screen_y as usize * SCREEN_WIDTH;
-                        let screen_pixel = self.screen[screen_pixel_index];
-                        if sprite_pixel && screen_pixel {
-                            self.register_v[0xF] = 1;
-                        }
-                        self.screen[screen_pixel_index] ^= sprite_pixel;
-                    }
+                self.draw_sprite(x, y, n)?;
+                if self.quirks.display_wait {
+                    self.wait_for_vblank = true;
                 }
-                self.draw_flag = true;
             }
             Instruction::InsEX9E(x) => {
                 if self.keypad_state_for_register(x)? {
@@ -317,22 +716,28 @@ where
                     self.skip_next_instruction()?;
                 }
             }
+            Instruction::InsF000(nnnn) => {
+                self.register_i = nnnn;
+            }
+            Instruction::InsFN01(n) => {
+                self.selected_planes = n & 0b11;
+            }
+            Instruction::InsF002 => {
+                for index in 0..AUDIO_PATTERN_SIZE {
+                    self.audio_pattern[index] =
+                        self.read_memory(self.register_i as usize + index)?;
+                }
+            }
             Instruction::InsFX07(x) => {
                 self.register_v[x as usize] = self.delay_timer;
             }
             Instruction::InsFX0A(x) => {
-                let mut any_key_pressed = false;
-                for (key_code, &key_pressed) in self.keypad.iter().enumerate() {
-                    if key_pressed {
-                        any_key_pressed = true;
-                        self.wait_for_key_release = Some(key_code);
-                        self.register_v[x as usize] = key_code as u8;
-                        break;
-                    }
-                }
-                if !any_key_pressed {
-                    self.repeat_current_instruction()?;
-                }
+                // Like the VIP, keys already held do not count and VX is only
+                // set once the key is released
+                self.key_wait = Some(KeyWait {
+                    register: x,
+                    key: None,
+                });
             }
             Instruction::InsFX15(x) => {
                 self.delay_timer = self.register_v[x as usize];
@@ -346,7 +751,17 @@ where
                     .wrapping_add(self.register_v[x as usize] as u16);
             }
             Instruction::InsFX29(x) => {
-                self.register_i = (self.register_v[x as usize] * 5) as u16;
+                self.register_i = (self.config.fontset_start
+                    + (self.register_v[x as usize] & 0xF) as usize * 5)
+                    as u16;
+            }
+            Instruction::InsFX30(x) => {
+                self.register_i = (self.config.big_fontset_start()
+                    + (self.register_v[x as usize] & 0xF) as usize * 10)
+                    as u16;
+            }
+            Instruction::InsFX3A(x) => {
+                self.pitch = self.register_v[x as usize];
             }
             Instruction::InsFX33(x) => {
                 let hundreds = self.register_v[x as usize] / 100;
@@ -361,14 +776,24 @@ where
                     let address = self.register_i as usize + index as usize;
                     self.write_memory(address, self.register_v[index as usize])?;
                 }
-                self.register_i = self.register_i.wrapping_add(x as u16 + 1);
+                let increment = self.quirks.memory_increment.amount(x);
+                self.register_i = self.register_i.wrapping_add(increment);
             }
             Instruction::InsFX65(x) => {
                 for index in 0..=x {
                     let address = self.register_i as usize + index as usize;
                     self.register_v[index as usize] = self.read_memory(address)?;
                 }
-                self.register_i = self.register_i.wrapping_add(x as u16 + 1);
+                let increment = self.quirks.memory_increment.amount(x);
+                self.register_i = self.register_i.wrapping_add(increment);
+            }
+            Instruction::InsFX75(x) => {
+                let count = x as usize + 1;
+                self.rpl_flags[..count].copy_from_slice(&self.register_v[..count]);
+            }
+            Instruction::InsFX85(x) => {
+                let count = x as usize + 1;
+                self.register_v[..count].copy_from_slice(&self.rpl_flags[..count]);
             }
         }
         Ok(())
@@ -378,22 +803,66 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rng::FixedSequence;
 
-    fn new_chip8() -> Chip8<core::iter::Repeat<u16>> {
-        Chip8::new(core::iter::repeat(0))
+    fn new_chip8() -> Chip8<FixedSequence<'static>> {
+        Chip8::new(FixedSequence::new(&[0]))
     }
 
     #[test]
     fn load_rom_rejects_too_large_buffer() {
         let mut chip8 = new_chip8();
-        let buffer = [0_u8; MEMORY_SIZE - ROM_START + 1];
+        let buffer = [0_u8; MEMORY_SIZE - PROGRAM_START as usize + 1];
         assert_eq!(
             chip8.load_rom(&buffer),
             Err(InstructionError::RomTooLarge {
                 rom_size: buffer.len(),
-                max_size: MEMORY_SIZE - ROM_START,
+                max_size: MEMORY_SIZE - PROGRAM_START as usize,
+            })
+        );
+    }
+
+    #[test]
+    fn machine_config_moves_program_and_fonts() {
+        let config = MachineConfig {
+            fontset_start: 0x100,
+            ..MachineConfig::ETI660
+        };
+        let mut chip8 =
+            Chip8::with_config(FixedSequence::new(&[0]), Quirks::default(), config).unwrap();
+        chip8.load_fontset();
+        // 6A0B, FA29, FA30
+        chip8
+            .load_rom(&[0x6A, 0x0B, 0xFA, 0x29, 0xFA, 0x30])
+            .unwrap();
+        assert_eq!(chip8.program_counter, 0x600);
+        assert_eq!(chip8.memory[0x100..0x105], Fontset::OCTO.small[..5]);
+
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+        assert_eq!(chip8.register_i, 0x100 + 0xB * 5);
+        chip8.tick().unwrap();
+        assert_eq!(chip8.register_i as usize, 0x100 + FONTSET_SIZE + 0xB * 10);
+
+        let rom = [0; MEMORY_SIZE - 0x600 + 1];
+        assert_eq!(
+            chip8.load_rom(&rom),
+            Err(InstructionError::RomTooLarge {
+                rom_size: rom.len(),
+                max_size: MEMORY_SIZE - 0x600,
+            })
+        );
+        assert_eq!(
+            chip8.set_config(MachineConfig {
+                fontset_start: 0x5F0,
+                ..config
+            }),
+            Err(ConfigError::FontsetOverlapsProgram {
+                fontset_start: 0x5F0,
+                program_start: 0x600
             })
         );
+        assert_eq!(chip8.config(), config);
     }
 
     #[test]
@@ -427,6 +896,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn tick_errors_carry_machine_context() {
+        let mut chip8 = new_chip8();
+        // 0x200 CALL 0x200
+        chip8.load_rom(&[0x22, 0x00]).unwrap();
+        chip8.register_i = 0x123;
+        for _ in 0..STACK_SIZE {
+            chip8.tick().unwrap();
+        }
+        let error = chip8.tick().unwrap_err();
+        assert_eq!(error.error, InstructionError::StackOverflow);
+        assert_eq!(error.program_counter, 0x200);
+        assert_eq!(error.opcode, Some(0x2200));
+        assert_eq!(error.instruction, Some(Instruction::Ins2NNN(0x200)));
+        assert_eq!(error.register_i, 0x123);
+        assert_eq!(error.stack_pointer, STACK_SIZE as u8);
+        assert_eq!(error.call_stack(), &[0x202; 4]);
+        #[cfg(feature = "alloc")]
+        assert_eq!(
+            alloc::format!("{error}"),
+            "StackOverflow at PC 0x0200: 2200 (CALL 0x200), I 0x0123, SP 16, \
+             call stack 0x0202 0x0202 0x0202 0x0202 ..."
+        );
+
+        chip8.program_counter = (MEMORY_SIZE - 1) as u16;
+        let error = chip8.tick().unwrap_err();
+        assert_eq!((error.opcode, error.instruction), (None, None));
+    }
+
     #[test]
     fn execute_ex9e_rejects_invalid_key_index() {
         let mut chip8 = new_chip8();
@@ -447,4 +945,474 @@ mod tests {
             Err(InstructionError::MemoryOutOfBounds(MEMORY_SIZE))
         );
     }
+
+    fn new_chip8_with_quirks(quirks: Quirks) -> Chip8<FixedSequence<'static>> {
+        Chip8::with_quirks(FixedSequence::new(&[0]), quirks)
+    }
+
+    #[test]
+    fn vf_reset_quirk_controls_logic_ops() {
+        for (quirks, expected_vf) in [(Quirks::VIP, 0), (Quirks::SCHIP11, 0xAA)] {
+            let mut chip8 = new_chip8_with_quirks(quirks);
+            chip8.register_v[0] = 0b1100;
+            chip8.register_v[1] = 0b1010;
+            chip8.register_v[0xF] = 0xAA;
+            chip8
+                .execute_instruction(&Instruction::Ins8XY1(0, 1))
+                .unwrap();
+            assert_eq!(chip8.register_v[0], 0b1110);
+            assert_eq!(chip8.register_v[0xF], expected_vf);
+        }
+    }
+
+    #[test]
+    fn shift_uses_vy_quirk_controls_shift_source() {
+        for (quirks, expected) in [(Quirks::VIP, 0b0100), (Quirks::CHIP48, 0b1000)] {
+            let mut chip8 = new_chip8_with_quirks(quirks);
+            chip8.register_v[0] = 0b1_0000;
+            chip8.register_v[1] = 0b1000;
+            chip8
+                .execute_instruction(&Instruction::Ins8XY6(0, 1))
+                .unwrap();
+            assert_eq!(chip8.register_v[0], expected);
+            assert_eq!(chip8.register_v[0xF], 0);
+        }
+    }
+
+    #[test]
+    fn memory_increment_quirk_controls_fx55_and_fx65() {
+        for (quirks, expected_i) in [
+            (Quirks::VIP, 0x304),
+            (Quirks::CHIP48, 0x303),
+            (Quirks::SCHIP11, 0x300),
+        ] {
+            let mut chip8 = new_chip8_with_quirks(quirks);
+            chip8.register_i = 0x300;
+            chip8.register_v[..4].copy_from_slice(&[1, 2, 3, 4]);
+            chip8.execute_instruction(&Instruction::InsFX55(3)).unwrap();
+            assert_eq!(&chip8.memory[0x300..0x304], &[1, 2, 3, 4]);
+            assert_eq!(chip8.register_i, expected_i);
+
+            chip8.register_i = 0x300;
+            chip8.register_v[..4].fill(0);
+            chip8.execute_instruction(&Instruction::InsFX65(3)).unwrap();
+            assert_eq!(&chip8.register_v[..4], &[1, 2, 3, 4]);
+            assert_eq!(chip8.register_i, expected_i);
+        }
+    }
+
+    #[test]
+    fn jump_uses_vx_quirk_controls_bnnn_offset_register() {
+        for (quirks, expected_pc) in [(Quirks::VIP, 0x311), (Quirks::SCHIP11, 0x333)] {
+            let mut chip8 = new_chip8_with_quirks(quirks);
+            chip8.register_v[0] = 0x11;
+            chip8.register_v[3] = 0x33;
+            chip8
+                .execute_instruction(&Instruction::InsBNNN(0x300))
+                .unwrap();
+            assert_eq!(chip8.program_counter, expected_pc);
+        }
+    }
+
+    #[test]
+    fn sprite_wrap_quirk_controls_screen_edge_behaviour() {
+        for (quirks, wrapped_pixel) in [(Quirks::VIP, false), (Quirks::XOCHIP, true)] {
+            let mut chip8 = new_chip8_with_quirks(quirks);
+            chip8.register_i = 0x300;
+            chip8.memory[0x300] = 0b1100_0000;
+            chip8.memory[0x301] = 0b1100_0000;
+            chip8.register_v[0] = (LORES_SCREEN_WIDTH - 1) as u8;
+            chip8.register_v[1] = (LORES_SCREEN_HEIGHT - 1) as u8;
+            chip8
+                .execute_instruction(&Instruction::InsDXYN(0, 1, 2))
+                .unwrap();
+            let last_row = LORES_SCREEN_HEIGHT - 1;
+            assert_eq!(chip8.screen.pixel(LORES_SCREEN_WIDTH - 1, last_row), 1);
+            assert_eq!(chip8.screen.pixel(0, last_row), wrapped_pixel as u8);
+            assert_eq!(
+                chip8.screen.pixel(LORES_SCREEN_WIDTH - 1, 0),
+                wrapped_pixel as u8
+            );
+            assert_eq!(chip8.screen.pixel(0, 0), wrapped_pixel as u8);
+            assert_eq!(chip8.screen.pixel(LORES_SCREEN_WIDTH, last_row), 0);
+        }
+    }
+
+    #[test]
+    fn display_wait_quirk_blocks_until_next_timer_tick() {
+        for (quirks, blocked) in [(Quirks::VIP, true), (Quirks::SCHIP11, false)] {
+            let mut chip8 = new_chip8_with_quirks(quirks);
+            // D001, 6005
+            chip8.load_rom(&[0xD0, 0x01, 0x60, 0x05]).unwrap();
+            chip8.tick().unwrap();
+            chip8.tick().unwrap();
+            assert_eq!(chip8.register_v[0] == 0, blocked);
+
+            chip8.tick_timer();
+            if blocked {
+                chip8.tick().unwrap();
+            }
+            assert_eq!(chip8.register_v[0], 5);
+        }
+    }
+
+    #[test]
+    fn execute_00ff_and_00fe_switch_resolution_and_clear_screen() {
+        let mut chip8 = new_chip8();
+        chip8.screen.set_pixel(0, 0, 1);
+        chip8.execute_instruction(&Instruction::Ins00FF).unwrap();
+        assert!(chip8.hires);
+        assert_eq!(
+            (chip8.screen_width(), chip8.screen_height()),
+            (SCREEN_WIDTH, SCREEN_HEIGHT)
+        );
+        assert_eq!(chip8.screen.pixel(0, 0), 0);
+
+        chip8.screen.set_pixel(0, 0, 1);
+        chip8.execute_instruction(&Instruction::Ins00FE).unwrap();
+        assert!(!chip8.hires);
+        assert_eq!(
+            (chip8.screen_width(), chip8.screen_height()),
+            (LORES_SCREEN_WIDTH, LORES_SCREEN_HEIGHT)
+        );
+        assert_eq!(chip8.screen.pixel(0, 0), 0);
+    }
+
+    #[test]
+    fn execute_dxy0_draws_16x16_sprite() {
+        let mut chip8 = new_chip8_with_quirks(Quirks::SCHIP11);
+        chip8.hires = true;
+        chip8.register_i = 0x300;
+        for row in 0..16 {
+            chip8.memory[0x300 + row * 2] = 0x80;
+            chip8.memory[0x300 + row * 2 + 1] = 0x01;
+        }
+        chip8.register_v[0] = 100;
+        chip8.register_v[1] = 40;
+        chip8
+            .execute_instruction(&Instruction::InsDXYN(0, 1, 0))
+            .unwrap();
+        for row in 0..16 {
+            assert_eq!(chip8.screen.pixel(100, 40 + row), 1);
+            assert_eq!(chip8.screen.pixel(101, 40 + row), 0);
+            assert_eq!(chip8.screen.pixel(115, 40 + row), 1);
+        }
+        assert_eq!(chip8.register_v[0xF], 0);
+
+        chip8
+            .execute_instruction(&Instruction::InsDXYN(0, 1, 0))
+            .unwrap();
+        assert_eq!(chip8.register_v[0xF], 1);
+        assert!(chip8.screen.is_blank());
+    }
+
+    #[test]
+    fn execute_scroll_instructions_move_screen_contents() {
+        let mut chip8 = new_chip8();
+        chip8.hires = true;
+        chip8.screen.set_pixel(10, 10, 1);
+
+        chip8.execute_instruction(&Instruction::Ins00CN(3)).unwrap();
+        assert_eq!(chip8.screen.pixel(10, 13), 1);
+
+        chip8.execute_instruction(&Instruction::Ins00FB).unwrap();
+        assert_eq!(chip8.screen.pixel(14, 13), 1);
+
+        chip8.execute_instruction(&Instruction::Ins00FC).unwrap();
+        chip8.execute_instruction(&Instruction::Ins00FC).unwrap();
+        assert_eq!(chip8.screen.pixel(6, 13), 1);
+        let lit = (0..SCREEN_HEIGHT)
+            .map(|y| chip8.screen.row(0, y).count_ones())
+            .sum::<u32>();
+        assert_eq!(lit, 1);
+
+        chip8.execute_instruction(&Instruction::Ins00FC).unwrap();
+        chip8.execute_instruction(&Instruction::Ins00FC).unwrap();
+        assert!(chip8.screen.is_blank());
+    }
+
+    #[test]
+    fn execute_fx30_points_i_at_big_font_digit() {
+        let mut chip8 = new_chip8();
+        chip8.load_fontset();
+        chip8.register_v[2] = 7;
+        chip8.execute_instruction(&Instruction::InsFX30(2)).unwrap();
+        assert_eq!(chip8.register_i as usize, FONTSET_START + FONTSET_SIZE + 70);
+        assert_eq!(chip8.memory[chip8.register_i as usize], 0xFF);
+    }
+
+    #[test]
+    fn execute_fx75_and_fx85_round_trip_rpl_flags() {
+        let mut chip8 = new_chip8();
+        chip8.register_v[..4].copy_from_slice(&[9, 8, 7, 6]);
+        chip8.execute_instruction(&Instruction::InsFX75(3)).unwrap();
+        chip8.register_v[..4].fill(0);
+        chip8.execute_instruction(&Instruction::InsFX85(2)).unwrap();
+        assert_eq!(&chip8.register_v[..4], &[9, 8, 7, 0]);
+    }
+
+    #[test]
+    fn execute_00fd_stops_execution() {
+        let mut chip8 = new_chip8();
+        // 00FD, 6005
+        chip8.load_rom(&[0x00, 0xFD, 0x60, 0x05]).unwrap();
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+        assert!(chip8.exited);
+        assert_eq!(chip8.register_v[0], 0);
+        assert_eq!(chip8.program_counter, PROGRAM_START + 2);
+    }
+
+    #[test]
+    fn tick_decodes_f000_nnnn_and_skips_over_it() {
+        let mut chip8 = new_chip8_with_quirks(Quirks::XOCHIP);
+        chip8.set_config(MachineConfig::XOCHIP).unwrap();
+        // F000 1234, 3000, F000 5678, 6105
+        chip8
+            .load_rom(&[
+                0xF0, 0x00, 0x12, 0x34, 0x30, 0x00, 0xF0, 0x00, 0x56, 0x78, 0x61, 0x05,
+            ])
+            .unwrap();
+        chip8.tick().unwrap();
+        assert_eq!(chip8.register_i, 0x1234);
+        chip8.tick().unwrap();
+        assert_eq!(chip8.program_counter, PROGRAM_START + 10);
+        chip8.tick().unwrap();
+        assert_eq!(chip8.register_v[1], 5);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn decode_cache_sees_self_modifying_code() {
+        let mut chip8 = new_chip8();
+        chip8.enable_decode_cache();
+        // 6005, 1200
+        chip8.load_rom(&[0x60, 0x05, 0x12, 0x00]).unwrap();
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+        assert_eq!(chip8.register_v[0], 5);
+
+        // FX55 rewrites the first instruction to 6007
+        chip8.register_i = 0x200;
+        chip8.register_v[..2].copy_from_slice(&[0x60, 0x07]);
+        chip8.execute_instruction(&Instruction::InsFX55(1)).unwrap();
+        chip8.tick().unwrap();
+        assert_eq!(chip8.register_v[0], 7);
+
+        // FX33 writes 2, 5, 5 from the low byte of 6007 onwards
+        chip8.register_i = 0x201;
+        chip8.register_v[0] = 255;
+        chip8.execute_instruction(&Instruction::InsFX33(0)).unwrap();
+        chip8.program_counter = 0x200;
+        chip8.tick().unwrap();
+        assert_eq!(chip8.register_v[0], 2);
+    }
+
+    #[test]
+    fn xo_memory_size_allows_access_above_4k() {
+        let mut chip8 = new_chip8_with_quirks(Quirks::XOCHIP);
+        chip8.register_i = 0xFFF0;
+        chip8.register_v[0] = 42;
+        assert_eq!(
+            chip8.execute_instruction(&Instruction::InsFX55(0)),
+            Err(InstructionError::MemoryOutOfBounds(0xFFF0))
+        );
+
+        chip8.set_config(MachineConfig::XOCHIP).unwrap();
+        chip8.register_i = 0xFFF0;
+        chip8.register_v[0] = 42;
+        chip8.execute_instruction(&Instruction::InsFX55(0)).unwrap();
+        assert_eq!(chip8.memory[0xFFF0], 42);
+    }
+
+    #[test]
+    fn capacity_limits_the_machine_config() {
+        let mut chip8 = Chip8::<_, MEMORY_SIZE>::with_capacity(
+            FixedSequence::new(&[0]),
+            Quirks::VIP,
+            MachineConfig::CHIP8,
+        )
+        .unwrap();
+        assert_eq!(chip8.memory().len(), MEMORY_SIZE);
+        assert_eq!(
+            chip8.set_config(MachineConfig::XOCHIP),
+            Err(ConfigError::MemoryExceedsCapacity {
+                memory_size: XO_MEMORY_SIZE,
+                capacity: MEMORY_SIZE
+            })
+        );
+        assert_eq!(chip8.config(), MachineConfig::CHIP8);
+
+        let mut chip8 = new_chip8();
+        chip8.memory[0x300] = 7;
+        chip8.set_config(MachineConfig::XOCHIP).unwrap();
+        assert_eq!(chip8.memory().len(), XO_MEMORY_SIZE);
+        assert_eq!(chip8.memory()[0x300], 7);
+        chip8.memory_mut()[0xFFF0] = 42;
+        chip8.set_config(MachineConfig::CHIP8).unwrap();
+        chip8.set_config(MachineConfig::XOCHIP).unwrap();
+        assert_eq!(chip8.memory[0xFFF0], 0);
+    }
+
+    #[test]
+    fn execute_dxyn_draws_each_selected_plane_from_consecutive_data() {
+        let mut chip8 = new_chip8_with_quirks(Quirks::XOCHIP);
+        chip8.register_i = 0x300;
+        chip8.memory[0x300] = 0b1000_0000;
+        chip8.memory[0x301] = 0b1100_0000;
+        chip8.execute_instruction(&Instruction::InsFN01(3)).unwrap();
+        chip8
+            .execute_instruction(&Instruction::InsDXYN(0, 0, 1))
+            .unwrap();
+        assert_eq!(chip8.screen.pixel(0, 0), 0b11);
+        assert_eq!(chip8.screen.pixel(1, 0), 0b10);
+        assert_eq!(chip8.register_v[0xF], 0);
+
+        chip8.execute_instruction(&Instruction::InsFN01(2)).unwrap();
+        chip8
+            .execute_instruction(&Instruction::InsDXYN(0, 0, 1))
+            .unwrap();
+        assert_eq!(chip8.screen.pixel(0, 0), 0b01);
+        assert_eq!(chip8.register_v[0xF], 1);
+
+        chip8.execute_instruction(&Instruction::InsFN01(1)).unwrap();
+        chip8.execute_instruction(&Instruction::Ins00E0).unwrap();
+        assert_eq!(chip8.screen.pixel(0, 0), 0);
+        assert_eq!(chip8.screen.pixel(1, 0), 0b10);
+    }
+
+    #[test]
+    fn execute_00e0_and_scroll_only_touch_selected_planes() {
+        let mut chip8 = new_chip8_with_quirks(Quirks::XOCHIP);
+        chip8.screen.set_pixel(0, 1, 0b11);
+        chip8.selected_planes = 0b10;
+        chip8.execute_instruction(&Instruction::Ins00DN(1)).unwrap();
+        assert_eq!(chip8.screen.pixel(0, 0), 0b10);
+        assert_eq!(chip8.screen.pixel(0, 1), 0b01);
+
+        chip8.execute_instruction(&Instruction::Ins00E0).unwrap();
+        assert_eq!(chip8.screen.pixel(0, 0), 0);
+        assert_eq!(chip8.screen.pixel(0, 1), 0b01);
+    }
+
+    #[test]
+    fn execute_5xy2_and_5xy3_save_and_load_register_ranges() {
+        let mut chip8 = new_chip8_with_quirks(Quirks::XOCHIP);
+        chip8.register_i = 0x300;
+        chip8.register_v[2..6].copy_from_slice(&[1, 2, 3, 4]);
+        chip8
+            .execute_instruction(&Instruction::Ins5XY2(2, 5))
+            .unwrap();
+        assert_eq!(&chip8.memory[0x300..0x304], &[1, 2, 3, 4]);
+        chip8
+            .execute_instruction(&Instruction::Ins5XY2(5, 2))
+            .unwrap();
+        assert_eq!(&chip8.memory[0x300..0x304], &[4, 3, 2, 1]);
+        assert_eq!(chip8.register_i, 0x300);
+
+        chip8
+            .execute_instruction(&Instruction::Ins5XY3(8, 9))
+            .unwrap();
+        assert_eq!(&chip8.register_v[8..10], &[4, 3]);
+    }
+
+    #[test]
+    fn execute_f002_and_fx3a_set_audio_state() {
+        let mut chip8 = new_chip8_with_quirks(Quirks::XOCHIP);
+        chip8.register_i = 0x300;
+        for index in 0..AUDIO_PATTERN_SIZE {
+            chip8.memory[0x300 + index] = index as u8;
+        }
+        chip8.execute_instruction(&Instruction::InsF002).unwrap();
+        assert_eq!(chip8.audio_pattern[15], 15);
+
+        assert_eq!(chip8.pitch, DEFAULT_PITCH);
+        chip8.register_v[3] = 112;
+        chip8.execute_instruction(&Instruction::InsFX3A(3)).unwrap();
+        assert_eq!(chip8.pitch, 112);
+    }
+
+    #[test]
+    fn set_keys_round_trips_keypad_bitmask() {
+        let mut chip8 = new_chip8();
+        chip8.set_keys(0b1000_0000_0000_0101);
+        assert_eq!(chip8.keypad_bitmask(), 0b1000_0000_0000_0101);
+        assert!(chip8.keypad[0] && chip8.keypad[2] && chip8.keypad[0xF]);
+        chip8.set_keys(0b0100);
+        assert_eq!(chip8.keypad_bitmask(), 0b0100);
+    }
+
+    // FX0A into V3, then 6105
+    fn new_chip8_waiting_for_key() -> Chip8<FixedSequence<'static>> {
+        let mut chip8 = new_chip8();
+        chip8.load_rom(&[0xF3, 0x0A, 0x61, 0x05]).unwrap();
+        chip8.register_v[3] = 0xFF;
+        chip8.tick().unwrap();
+        chip8
+    }
+
+    #[test]
+    fn fx0a_stores_key_on_release() {
+        let mut chip8 = new_chip8_waiting_for_key();
+        assert!(chip8.is_waiting_for_key());
+        chip8.tick().unwrap();
+        assert_eq!(chip8.program_counter, PROGRAM_START + 2);
+
+        chip8.key_down(7);
+        chip8.tick().unwrap();
+        assert!(chip8.is_waiting_for_key());
+        assert_eq!(chip8.register_v[3], 0xFF);
+
+        chip8.key_up(7);
+        assert!(!chip8.is_waiting_for_key());
+        assert_eq!(chip8.register_v[3], 7);
+        chip8.tick().unwrap();
+        assert_eq!(chip8.register_v[1], 5);
+    }
+
+    #[test]
+    fn fx0a_ignores_keys_held_when_it_starts() {
+        let mut chip8 = new_chip8();
+        chip8.load_rom(&[0xF3, 0x0A]).unwrap();
+        chip8.key_down(2);
+        chip8.tick().unwrap();
+
+        chip8.key_up(2);
+        assert!(chip8.is_waiting_for_key());
+        chip8.key_down(2);
+        chip8.key_up(2);
+        assert!(!chip8.is_waiting_for_key());
+        assert_eq!(chip8.register_v[3], 2);
+    }
+
+    #[test]
+    fn fx0a_waits_for_the_first_key_pressed_of_several() {
+        let mut chip8 = new_chip8_waiting_for_key();
+        chip8.key_down(9);
+        chip8.key_down(4);
+        chip8.key_up(4);
+        assert!(chip8.is_waiting_for_key());
+        chip8.key_up(9);
+        assert_eq!(chip8.register_v[3], 9);
+
+        let mut chip8 = new_chip8_waiting_for_key();
+        chip8.set_keys(0b1010_0000);
+        chip8.set_keys(0b1000_0000);
+        assert_eq!(chip8.register_v[3], 5);
+        assert!(!chip8.is_waiting_for_key());
+    }
+
+    #[test]
+    fn key_events_ignore_repeats_and_invalid_keys() {
+        let mut chip8 = new_chip8();
+        chip8.load_rom(&[0xF3, 0x0A]).unwrap();
+        chip8.key_down(1);
+        chip8.tick().unwrap();
+        // Auto-repeat of a held key is not a new press
+        chip8.key_down(1);
+        chip8.key_down(KEYPAD_SIZE);
+        chip8.key_up(KEYPAD_SIZE);
+        chip8.key_up(1);
+        assert!(chip8.is_waiting_for_key());
+    }
 }
@@ -0,0 +1,555 @@
+// Breakpoints and watchpoints layered over Chip8::tick. Tables have a fixed
+// size so the debugger works without alloc.
+use crate::{
+    chip8::{AUDIO_PATTERN_SIZE, Chip8, NUM_REGISTERS, PLANE_COUNT},
+    error::{DebuggerError, ExecutionError},
+    instruction::Instruction,
+    quirks::MemoryIncrement,
+    rng::RandomSource,
+};
+
+pub const MAX_BREAKPOINTS: usize = 16;
+pub const MAX_WATCHPOINTS: usize = 16;
+
+// Bit of the I register in a register access mask, after V0..VF
+const REGISTER_I_BIT: u32 = 1 << NUM_REGISTERS;
+const REGISTER_VF_BIT: u32 = 1 << 0xF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl WatchKind {
+    fn matches(self, access: Access) -> bool {
+        matches!(
+            (self, access),
+            (WatchKind::ReadWrite, _)
+                | (WatchKind::Read, Access::Read)
+                | (WatchKind::Write, Access::Write)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchTarget {
+    // Inclusive address range
+    Memory { start: u16, end: u16 },
+    Register(u8),
+    RegisterI,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub target: WatchTarget,
+    pub kind: WatchKind,
+}
+
+// Data memory touched by an instruction, instruction fetches are not included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub access: Access,
+    pub start: u16,
+    pub len: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Step,
+    StepOver,
+    StepOut,
+    Cursor(u16),
+    // Execution stops before the instruction at the breakpoint
+    Breakpoint(u16),
+    // Execution stops after the accessing instruction at pc
+    Watchpoint {
+        watchpoint: Watchpoint,
+        access: Access,
+        pc: u16,
+    },
+    // Waiting for the next timer tick or for a key release
+    Blocked,
+    Exited,
+    BudgetExhausted,
+}
+
+#[derive(Clone, Copy)]
+enum Target {
+    Step,
+    StepOver,
+    StepOut,
+    Cursor(u16),
+    None,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Debugger {
+    breakpoints: [Option<u16>; MAX_BREAKPOINTS],
+    watchpoints: [Option<Watchpoint>; MAX_WATCHPOINTS],
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) -> Result<(), DebuggerError> {
+        if self.breakpoints().any(|breakpoint| breakpoint == address) {
+            return Ok(());
+        }
+        let slot = self
+            .breakpoints
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(DebuggerError::TooManyBreakpoints(MAX_BREAKPOINTS))?;
+        *slot = Some(address);
+        Ok(())
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        remove(&mut self.breakpoints, address)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().flatten().copied()
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> Result<(), DebuggerError> {
+        match watchpoint.target {
+            WatchTarget::Memory { start, end } if start > end => {
+                return Err(DebuggerError::InvalidWatchRange { start, end });
+            }
+            WatchTarget::Register(register) if register as usize >= NUM_REGISTERS => {
+                return Err(DebuggerError::InvalidRegister(register));
+            }
+            _ => {}
+        }
+        if self.watchpoints().any(|existing| existing == watchpoint) {
+            return Ok(());
+        }
+        let slot = self
+            .watchpoints
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(DebuggerError::TooManyWatchpoints(MAX_WATCHPOINTS))?;
+        *slot = Some(watchpoint);
+        Ok(())
+    }
+
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+        remove(&mut self.watchpoints, watchpoint)
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = Watchpoint> + '_ {
+        self.watchpoints.iter().flatten().copied()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn step<R, const CAPACITY: usize>(
+        &self,
+        chip8: &mut Chip8<R, CAPACITY>,
+    ) -> Result<StopReason, ExecutionError>
+    where
+        R: RandomSource,
+    {
+        self.run(chip8, Target::Step, 1)
+    }
+
+    // Runs a 2NNN call through to its return, any other instruction is a single step
+    pub fn step_over<R, const CAPACITY: usize>(
+        &self,
+        chip8: &mut Chip8<R, CAPACITY>,
+        budget: usize,
+    ) -> Result<StopReason, ExecutionError>
+    where
+        R: RandomSource,
+    {
+        self.run(chip8, Target::StepOver, budget)
+    }
+
+    // Runs until the 00EE that returns from the current subroutine
+    pub fn step_out<R, const CAPACITY: usize>(
+        &self,
+        chip8: &mut Chip8<R, CAPACITY>,
+        budget: usize,
+    ) -> Result<StopReason, ExecutionError>
+    where
+        R: RandomSource,
+    {
+        self.run(chip8, Target::StepOut, budget)
+    }
+
+    pub fn run_to<R, const CAPACITY: usize>(
+        &self,
+        chip8: &mut Chip8<R, CAPACITY>,
+        address: u16,
+        budget: usize,
+    ) -> Result<StopReason, ExecutionError>
+    where
+        R: RandomSource,
+    {
+        self.run(chip8, Target::Cursor(address), budget)
+    }
+
+    pub fn resume<R, const CAPACITY: usize>(
+        &self,
+        chip8: &mut Chip8<R, CAPACITY>,
+        budget: usize,
+    ) -> Result<StopReason, ExecutionError>
+    where
+        R: RandomSource,
+    {
+        self.run(chip8, Target::None, budget)
+    }
+
+    // Executes at most `budget` instructions. Breakpoints and the cursor are not
+    // checked for the first one, so resuming from a stop makes progress.
+    fn run<R, const CAPACITY: usize>(
+        &self,
+        chip8: &mut Chip8<R, CAPACITY>,
+        target: Target,
+        budget: usize,
+    ) -> Result<StopReason, ExecutionError>
+    where
+        R: RandomSource,
+    {
+        let depth = chip8.stack_pointer;
+        for executed in 0..budget {
+            if chip8.exited {
+                return Ok(StopReason::Exited);
+            }
+            if chip8.wait_for_vblank || chip8.is_waiting_for_key() {
+                return Ok(StopReason::Blocked);
+            }
+            let pc = chip8.program_counter;
+            if executed > 0 {
+                if let Target::Cursor(address) = target
+                    && address == pc
+                {
+                    return Ok(StopReason::Cursor(pc));
+                }
+                if self.breakpoints().any(|breakpoint| breakpoint == pc) {
+                    return Ok(StopReason::Breakpoint(pc));
+                }
+            }
+
+            let instruction = chip8
+                .peek_instruction()
+                .map_err(|error| chip8.execution_error(pc, error))?;
+            let hit = self.watch_hit(chip8, &instruction);
+            chip8.tick()?;
+            if let Some((watchpoint, access)) = hit {
+                return Ok(StopReason::Watchpoint {
+                    watchpoint,
+                    access,
+                    pc,
+                });
+            }
+
+            match target {
+                Target::Step => return Ok(StopReason::Step),
+                Target::StepOver if chip8.stack_pointer <= depth => {
+                    return Ok(StopReason::StepOver);
+                }
+                Target::StepOut if chip8.stack_pointer < depth => {
+                    return Ok(StopReason::StepOut);
+                }
+                _ => {}
+            }
+        }
+        Ok(StopReason::BudgetExhausted)
+    }
+
+    fn watch_hit<R, const CAPACITY: usize>(
+        &self,
+        chip8: &Chip8<R, CAPACITY>,
+        instruction: &Instruction,
+    ) -> Option<(Watchpoint, Access)>
+    where
+        R: RandomSource,
+    {
+        let (register_reads, register_writes) = register_accesses(chip8, instruction);
+        let memory = chip8.memory_access(instruction);
+        self.watchpoints().find_map(|watchpoint| {
+            let (read, written) = match watchpoint.target {
+                WatchTarget::Memory { start, end } => {
+                    let overlapping = memory.filter(|memory| {
+                        let last = memory.start as u32 + memory.len as u32 - 1;
+                        memory.start <= end && start as u32 <= last
+                    });
+                    match overlapping.map(|memory| memory.access) {
+                        Some(Access::Read) => (true, false),
+                        Some(Access::Write) => (false, true),
+                        None => (false, false),
+                    }
+                }
+                WatchTarget::Register(register) => {
+                    let bit = 1 << register;
+                    (register_reads & bit != 0, register_writes & bit != 0)
+                }
+                WatchTarget::RegisterI => (
+                    register_reads & REGISTER_I_BIT != 0,
+                    register_writes & REGISTER_I_BIT != 0,
+                ),
+            };
+            if written && watchpoint.kind.matches(Access::Write) {
+                Some((watchpoint, Access::Write))
+            } else if read && watchpoint.kind.matches(Access::Read) {
+                Some((watchpoint, Access::Read))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl<R, const CAPACITY: usize> Chip8<R, CAPACITY>
+where
+    R: RandomSource,
+{
+    pub fn memory_access(&self, instruction: &Instruction) -> Option<MemoryAccess> {
+        let (access, len) = match *instruction {
+            Instruction::Ins5XY2(x, y) => (Access::Write, x.abs_diff(y) as u16 + 1),
+            Instruction::Ins5XY3(x, y) => (Access::Read, x.abs_diff(y) as u16 + 1),
+            Instruction::InsDXYN(_, _, n) => {
+                // DXY0 reads a 16x16 sprite, each selected plane reads its own copy
+                let bytes = if n == 0 { 32 } else { n as u16 };
+                let planes = (self.selected_planes & ((1 << PLANE_COUNT) - 1)).count_ones();
+                if planes == 0 {
+                    return None;
+                }
+                (Access::Read, bytes * planes as u16)
+            }
+            Instruction::InsF002 => (Access::Read, AUDIO_PATTERN_SIZE as u16),
+            Instruction::InsFX33(_) => (Access::Write, 3),
+            Instruction::InsFX55(x) => (Access::Write, x as u16 + 1),
+            Instruction::InsFX65(x) => (Access::Read, x as u16 + 1),
+            _ => return None,
+        };
+        Some(MemoryAccess {
+            access,
+            start: self.register_i,
+            len,
+        })
+    }
+}
+
+// Masks of the registers an instruction reads and writes, V0..VF then I
+fn register_accesses<R, const CAPACITY: usize>(
+    chip8: &Chip8<R, CAPACITY>,
+    instruction: &Instruction,
+) -> (u32, u32)
+where
+    R: RandomSource,
+{
+    let v = |x: u8| 1_u32 << x;
+    let up_to = |x: u8| (1_u32 << (x + 1)) - 1;
+    let range = |x: u8, y: u8| up_to(x.max(y)) & !(up_to(x.min(y)) >> 1);
+    let quirks = chip8.quirks;
+    match *instruction {
+        Instruction::Ins3XNN(x, _) | Instruction::Ins4XNN(x, _) => (v(x), 0),
+        Instruction::Ins5XY0(x, y) | Instruction::Ins9XY0(x, y) => (v(x) | v(y), 0),
+        Instruction::Ins5XY2(x, y) => (range(x, y) | REGISTER_I_BIT, 0),
+        Instruction::Ins5XY3(x, y) => (REGISTER_I_BIT, range(x, y)),
+        Instruction::Ins6XNN(x, _) | Instruction::InsCXNN(x, _) => (0, v(x)),
+        Instruction::Ins7XNN(x, _) => (v(x), v(x)),
+        Instruction::Ins8XY0(x, y) => (v(y), v(x)),
+        Instruction::Ins8XY1(x, y) | Instruction::Ins8XY2(x, y) | Instruction::Ins8XY3(x, y) => {
+            let flag = if quirks.vf_reset { REGISTER_VF_BIT } else { 0 };
+            (v(x) | v(y), v(x) | flag)
+        }
+        Instruction::Ins8XY4(x, y) | Instruction::Ins8XY5(x, y) | Instruction::Ins8XY7(x, y) => {
+            (v(x) | v(y), v(x) | REGISTER_VF_BIT)
+        }
+        Instruction::Ins8XY6(x, y) | Instruction::Ins8XYE(x, y) => {
+            let source = if quirks.shift_uses_vy { v(y) } else { v(x) };
+            (source, v(x) | REGISTER_VF_BIT)
+        }
+        Instruction::InsANNN(_) | Instruction::InsF000(_) => (0, REGISTER_I_BIT),
+        Instruction::InsBNNN(nnn) => {
+            let x = if quirks.jump_uses_vx {
+                (nnn >> 8) as u8
+            } else {
+                0
+            };
+            (v(x), 0)
+        }
+        Instruction::InsDXYN(x, y, _) => (v(x) | v(y) | REGISTER_I_BIT, REGISTER_VF_BIT),
+        Instruction::InsEX9E(x) | Instruction::InsEXA1(x) => (v(x), 0),
+        Instruction::InsF002 => (REGISTER_I_BIT, 0),
+        Instruction::InsFX07(x) | Instruction::InsFX0A(x) => (0, v(x)),
+        Instruction::InsFX15(x) | Instruction::InsFX18(x) | Instruction::InsFX3A(x) => (v(x), 0),
+        Instruction::InsFX1E(x) => (v(x) | REGISTER_I_BIT, REGISTER_I_BIT),
+        Instruction::InsFX29(x) | Instruction::InsFX30(x) => (v(x), REGISTER_I_BIT),
+        Instruction::InsFX33(x) => (v(x) | REGISTER_I_BIT, 0),
+        Instruction::InsFX55(x) => {
+            let increment = if quirks.memory_increment == MemoryIncrement::None {
+                0
+            } else {
+                REGISTER_I_BIT
+            };
+            (up_to(x) | REGISTER_I_BIT, increment)
+        }
+        Instruction::InsFX65(x) => {
+            let increment = if quirks.memory_increment == MemoryIncrement::None {
+                0
+            } else {
+                REGISTER_I_BIT
+            };
+            (REGISTER_I_BIT, up_to(x) | increment)
+        }
+        Instruction::InsFX75(x) => (up_to(x), 0),
+        Instruction::InsFX85(x) => (0, up_to(x)),
+        _ => (0, 0),
+    }
+}
+
+fn remove<T: PartialEq>(slots: &mut [Option<T>], value: T) -> bool {
+    match slots.iter_mut().find(|slot| slot.as_ref() == Some(&value)) {
+        Some(slot) => {
+            *slot = None;
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Access, Debugger, StopReason, WatchKind, WatchTarget, Watchpoint};
+    use crate::{chip8::Chip8, error::DebuggerError, rng::FixedSequence};
+
+    // 0x200 CALL 0x206, 0x202 LD V3, 1, 0x204 JP 0x204,
+    // 0x206 LD I, 0x300, 0x208 LD B, V0, 0x20A RET
+    const ROM: [u8; 12] = [
+        0x22, 0x06, 0x63, 0x01, 0x12, 0x04, 0xA3, 0x00, 0xF0, 0x33, 0x00, 0xEE,
+    ];
+
+    fn new_chip8() -> Chip8<FixedSequence<'static>> {
+        let mut chip8 = Chip8::new(FixedSequence::new(&[0]));
+        chip8.load_rom(&ROM).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn breakpoints_stop_before_execution_and_resume_past_them() {
+        let mut chip8 = new_chip8();
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x208).unwrap();
+        assert_eq!(
+            debugger.resume(&mut chip8, 100),
+            Ok(StopReason::Breakpoint(0x208))
+        );
+        assert_eq!(chip8.program_counter, 0x208);
+        assert_eq!(
+            debugger.resume(&mut chip8, 100),
+            Ok(StopReason::BudgetExhausted)
+        );
+
+        assert!(debugger.remove_breakpoint(0x208));
+        assert!(!debugger.remove_breakpoint(0x208));
+        for address in 0..16 {
+            debugger.add_breakpoint(address).unwrap();
+        }
+        assert_eq!(
+            debugger.add_breakpoint(0x300),
+            Err(DebuggerError::TooManyBreakpoints(16))
+        );
+    }
+
+    #[test]
+    fn step_over_and_step_out_follow_the_call_stack() {
+        let mut chip8 = new_chip8();
+        let debugger = Debugger::new();
+        assert_eq!(
+            debugger.step_over(&mut chip8, 100),
+            Ok(StopReason::StepOver)
+        );
+        assert_eq!((chip8.program_counter, chip8.stack_pointer), (0x202, 0));
+
+        let mut chip8 = new_chip8();
+        assert_eq!(debugger.step(&mut chip8), Ok(StopReason::Step));
+        assert_eq!(chip8.program_counter, 0x206);
+        assert_eq!(debugger.step_out(&mut chip8, 100), Ok(StopReason::StepOut));
+        assert_eq!(chip8.program_counter, 0x202);
+
+        assert_eq!(
+            debugger.run_to(&mut chip8, 0x204, 100),
+            Ok(StopReason::Cursor(0x204))
+        );
+    }
+
+    #[test]
+    fn watchpoints_report_matching_accesses_after_execution() {
+        let mut chip8 = new_chip8();
+        let mut debugger = Debugger::new();
+        let read = Watchpoint {
+            target: WatchTarget::Memory {
+                start: 0x302,
+                end: 0x3FF,
+            },
+            kind: WatchKind::Read,
+        };
+        let write = Watchpoint {
+            kind: WatchKind::Write,
+            ..read
+        };
+        debugger.add_watchpoint(read).unwrap();
+        debugger.add_watchpoint(write).unwrap();
+        assert_eq!(
+            debugger.resume(&mut chip8, 100),
+            Ok(StopReason::Watchpoint {
+                watchpoint: write,
+                access: Access::Write,
+                pc: 0x208,
+            })
+        );
+        assert_eq!(chip8.program_counter, 0x20A);
+
+        let mut chip8 = new_chip8();
+        let mut debugger = Debugger::new();
+        let register = Watchpoint {
+            target: WatchTarget::Register(3),
+            kind: WatchKind::ReadWrite,
+        };
+        debugger.add_watchpoint(register).unwrap();
+        assert_eq!(
+            debugger.resume(&mut chip8, 100),
+            Ok(StopReason::Watchpoint {
+                watchpoint: register,
+                access: Access::Write,
+                pc: 0x202,
+            })
+        );
+        assert_eq!(chip8.register_v[3], 1);
+
+        assert_eq!(
+            debugger.add_watchpoint(Watchpoint {
+                target: WatchTarget::Register(16),
+                kind: WatchKind::Read,
+            }),
+            Err(DebuggerError::InvalidRegister(16))
+        );
+    }
+
+    #[test]
+    fn stops_when_the_machine_blocks_or_exits() {
+        let mut chip8 = new_chip8();
+        let debugger = Debugger::new();
+        chip8.wait_for_vblank = true;
+        assert_eq!(debugger.resume(&mut chip8, 100), Ok(StopReason::Blocked));
+        chip8.exited = true;
+        assert_eq!(debugger.step(&mut chip8), Ok(StopReason::Exited));
+    }
+}
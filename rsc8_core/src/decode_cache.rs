@@ -0,0 +1,78 @@
+use crate::instruction::Instruction;
+use alloc::{vec, vec::Vec};
+
+// Longest instruction in bytes, a write can change any instruction starting
+// up to this many bytes before it
+const MAX_INSTRUCTION_SIZE: usize = 4;
+
+// Decoded instructions indexed by the address they start at. Entries are
+// dropped when a byte they were decoded from is written.
+pub struct DecodeCache {
+    entries: Vec<Option<Instruction>>,
+}
+
+impl DecodeCache {
+    pub fn new(memory_size: usize) -> Self {
+        Self {
+            entries: vec![None; memory_size],
+        }
+    }
+
+    pub fn get(&self, address: u16) -> Option<Instruction> {
+        self.entries.get(address as usize).copied().flatten()
+    }
+
+    pub fn insert(&mut self, address: u16, instruction: Instruction) {
+        if let Some(entry) = self.entries.get_mut(address as usize) {
+            *entry = Some(instruction);
+        }
+    }
+
+    // Call after the byte at address changes
+    pub fn invalidate(&mut self, address: usize) {
+        let start = address.saturating_sub(MAX_INSTRUCTION_SIZE - 1);
+        let end = (address + 1).min(self.entries.len());
+        if let Some(entries) = self.entries.get_mut(start..end) {
+            entries.fill(None);
+        }
+    }
+
+    // Drops every entry, for a memory of memory_size bytes
+    pub fn reset(&mut self, memory_size: usize) {
+        self.entries.clear();
+        self.entries.resize(memory_size, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DecodeCache;
+    use crate::{chip8::XO_MEMORY_SIZE, instruction::Instruction};
+
+    #[test]
+    fn writes_invalidate_every_instruction_covering_the_byte() {
+        let mut cache = DecodeCache::new(XO_MEMORY_SIZE);
+        cache.insert(0x200, Instruction::InsF000(0x1234));
+        cache.insert(0x204, Instruction::Ins00E0);
+
+        cache.invalidate(0x204);
+        assert_eq!(cache.get(0x200), Some(Instruction::InsF000(0x1234)));
+        assert_eq!(cache.get(0x204), None);
+
+        cache.invalidate(0x203);
+        assert_eq!(cache.get(0x200), None);
+
+        cache.insert(0xFFFF, Instruction::Ins00E0);
+        cache.invalidate(0xFFFF);
+        cache.invalidate(0x10000);
+        assert_eq!(cache.get(0xFFFF), None);
+    }
+
+    #[test]
+    fn addresses_past_the_memory_size_are_never_cached() {
+        let mut cache = DecodeCache::new(0x1000);
+        cache.insert(0x1000, Instruction::Ins00E0);
+        assert_eq!(cache.get(0x1000), None);
+        assert_eq!(cache.get(0xFFFF), None);
+    }
+}
@@ -0,0 +1,524 @@
+use crate::instruction::Instruction;
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    Standard,
+    Octo,
+}
+
+// Renders an instruction in Octo syntax, see Instruction::octo
+pub struct Octo<'a>(&'a Instruction);
+
+impl Instruction {
+    pub fn octo(&self) -> Octo<'_> {
+        Octo(self)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::Ins00CN(n) => write!(f, "SCD {n}"),
+            Instruction::Ins00DN(n) => write!(f, "SCU {n}"),
+            Instruction::Ins00E0 => write!(f, "CLS"),
+            Instruction::Ins00EE => write!(f, "RET"),
+            Instruction::Ins00FB => write!(f, "SCR"),
+            Instruction::Ins00FC => write!(f, "SCL"),
+            Instruction::Ins00FD => write!(f, "EXIT"),
+            Instruction::Ins00FE => write!(f, "LOW"),
+            Instruction::Ins00FF => write!(f, "HIGH"),
+            Instruction::Ins1NNN(nnn) => write!(f, "JP 0x{nnn:03X}"),
+            Instruction::Ins2NNN(nnn) => write!(f, "CALL 0x{nnn:03X}"),
+            Instruction::Ins3XNN(x, nn) => write!(f, "SE V{x:X}, 0x{nn:02X}"),
+            Instruction::Ins4XNN(x, nn) => write!(f, "SNE V{x:X}, 0x{nn:02X}"),
+            Instruction::Ins5XY0(x, y) => write!(f, "SE V{x:X}, V{y:X}"),
+            Instruction::Ins5XY2(x, y) => write!(f, "SAVE V{x:X}, V{y:X}"),
+            Instruction::Ins5XY3(x, y) => write!(f, "LOAD V{x:X}, V{y:X}"),
+            Instruction::Ins6XNN(x, nn) => write!(f, "LD V{x:X}, 0x{nn:02X}"),
+            Instruction::Ins7XNN(x, nn) => write!(f, "ADD V{x:X}, 0x{nn:02X}"),
+            Instruction::Ins8XY0(x, y) => write!(f, "LD V{x:X}, V{y:X}"),
+            Instruction::Ins8XY1(x, y) => write!(f, "OR V{x:X}, V{y:X}"),
+            Instruction::Ins8XY2(x, y) => write!(f, "AND V{x:X}, V{y:X}"),
+            Instruction::Ins8XY3(x, y) => write!(f, "XOR V{x:X}, V{y:X}"),
+            Instruction::Ins8XY4(x, y) => write!(f, "ADD V{x:X}, V{y:X}"),
+            Instruction::Ins8XY5(x, y) => write!(f, "SUB V{x:X}, V{y:X}"),
+            Instruction::Ins8XY6(x, y) => write!(f, "SHR V{x:X}, V{y:X}"),
+            Instruction::Ins8XY7(x, y) => write!(f, "SUBN V{x:X}, V{y:X}"),
+            Instruction::Ins8XYE(x, y) => write!(f, "SHL V{x:X}, V{y:X}"),
+            Instruction::Ins9XY0(x, y) => write!(f, "SNE V{x:X}, V{y:X}"),
+            Instruction::InsANNN(nnn) => write!(f, "LD I, 0x{nnn:03X}"),
+            Instruction::InsBNNN(nnn) => write!(f, "JP V0, 0x{nnn:03X}"),
+            Instruction::InsCXNN(x, nn) => write!(f, "RND V{x:X}, 0x{nn:02X}"),
+            Instruction::InsDXYN(x, y, n) => write!(f, "DRW V{x:X}, V{y:X}, {n}"),
+            Instruction::InsEX9E(x) => write!(f, "SKP V{x:X}"),
+            Instruction::InsEXA1(x) => write!(f, "SKNP V{x:X}"),
+            Instruction::InsF000(nnnn) => write!(f, "LD I, 0x{nnnn:04X}"),
+            Instruction::InsFN01(n) => write!(f, "PLANE {n}"),
+            Instruction::InsF002 => write!(f, "AUDIO"),
+            Instruction::InsFX07(x) => write!(f, "LD V{x:X}, DT"),
+            Instruction::InsFX0A(x) => write!(f, "LD V{x:X}, K"),
+            Instruction::InsFX15(x) => write!(f, "LD DT, V{x:X}"),
+            Instruction::InsFX18(x) => write!(f, "LD ST, V{x:X}"),
+            Instruction::InsFX1E(x) => write!(f, "ADD I, V{x:X}"),
+            Instruction::InsFX29(x) => write!(f, "LD F, V{x:X}"),
+            Instruction::InsFX30(x) => write!(f, "LD HF, V{x:X}"),
+            Instruction::InsFX33(x) => write!(f, "LD B, V{x:X}"),
+            Instruction::InsFX3A(x) => write!(f, "PITCH V{x:X}"),
+            Instruction::InsFX55(x) => write!(f, "LD [I], V{x:X}"),
+            Instruction::InsFX65(x) => write!(f, "LD V{x:X}, [I]"),
+            Instruction::InsFX75(x) => write!(f, "LD R, V{x:X}"),
+            Instruction::InsFX85(x) => write!(f, "LD V{x:X}, R"),
+        }
+    }
+}
+
+impl fmt::Display for Octo<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self.0 {
+            Instruction::Ins00CN(n) => write!(f, "scroll-down {n}"),
+            Instruction::Ins00DN(n) => write!(f, "scroll-up {n}"),
+            Instruction::Ins00E0 => write!(f, "clear"),
+            Instruction::Ins00EE => write!(f, "return"),
+            Instruction::Ins00FB => write!(f, "scroll-right"),
+            Instruction::Ins00FC => write!(f, "scroll-left"),
+            Instruction::Ins00FD => write!(f, "exit"),
+            Instruction::Ins00FE => write!(f, "lores"),
+            Instruction::Ins00FF => write!(f, "hires"),
+            Instruction::Ins1NNN(nnn) => write!(f, "jump 0x{nnn:03X}"),
+            Instruction::Ins2NNN(nnn) => write!(f, ":call 0x{nnn:03X}"),
+            // Octo's `if` runs the next statement when the condition holds,
+            // so every skip instruction is written with the inverse condition
+            Instruction::Ins3XNN(x, nn) => write!(f, "if v{x:x} != 0x{nn:02X} then"),
+            Instruction::Ins4XNN(x, nn) => write!(f, "if v{x:x} == 0x{nn:02X} then"),
+            Instruction::Ins5XY0(x, y) => write!(f, "if v{x:x} != v{y:x} then"),
+            Instruction::Ins5XY2(x, y) => write!(f, "save v{x:x} - v{y:x}"),
+            Instruction::Ins5XY3(x, y) => write!(f, "load v{x:x} - v{y:x}"),
+            Instruction::Ins6XNN(x, nn) => write!(f, "v{x:x} := 0x{nn:02X}"),
+            Instruction::Ins7XNN(x, nn) => write!(f, "v{x:x} += 0x{nn:02X}"),
+            Instruction::Ins8XY0(x, y) => write!(f, "v{x:x} := v{y:x}"),
+            Instruction::Ins8XY1(x, y) => write!(f, "v{x:x} |= v{y:x}"),
+            Instruction::Ins8XY2(x, y) => write!(f, "v{x:x} &= v{y:x}"),
+            Instruction::Ins8XY3(x, y) => write!(f, "v{x:x} ^= v{y:x}"),
+            Instruction::Ins8XY4(x, y) => write!(f, "v{x:x} += v{y:x}"),
+            Instruction::Ins8XY5(x, y) => write!(f, "v{x:x} -= v{y:x}"),
+            Instruction::Ins8XY6(x, y) => write!(f, "v{x:x} >>= v{y:x}"),
+            Instruction::Ins8XY7(x, y) => write!(f, "v{x:x} =- v{y:x}"),
+            Instruction::Ins8XYE(x, y) => write!(f, "v{x:x} <<= v{y:x}"),
+            Instruction::Ins9XY0(x, y) => write!(f, "if v{x:x} == v{y:x} then"),
+            Instruction::InsANNN(nnn) => write!(f, "i := 0x{nnn:03X}"),
+            Instruction::InsBNNN(nnn) => write!(f, "jump0 0x{nnn:03X}"),
+            Instruction::InsCXNN(x, nn) => write!(f, "v{x:x} := random 0x{nn:02X}"),
+            Instruction::InsDXYN(x, y, n) => write!(f, "sprite v{x:x} v{y:x} {n}"),
+            Instruction::InsEX9E(x) => write!(f, "if v{x:x} -key then"),
+            Instruction::InsEXA1(x) => write!(f, "if v{x:x} key then"),
+            Instruction::InsF000(nnnn) => write!(f, "i := long 0x{nnnn:04X}"),
+            Instruction::InsFN01(n) => write!(f, "plane {n}"),
+            Instruction::InsF002 => write!(f, "audio"),
+            Instruction::InsFX07(x) => write!(f, "v{x:x} := delay"),
+            Instruction::InsFX0A(x) => write!(f, "v{x:x} := key"),
+            Instruction::InsFX15(x) => write!(f, "delay := v{x:x}"),
+            Instruction::InsFX18(x) => write!(f, "buzzer := v{x:x}"),
+            Instruction::InsFX1E(x) => write!(f, "i += v{x:x}"),
+            Instruction::InsFX29(x) => write!(f, "i := hex v{x:x}"),
+            Instruction::InsFX30(x) => write!(f, "i := bighex v{x:x}"),
+            Instruction::InsFX33(x) => write!(f, "bcd v{x:x}"),
+            Instruction::InsFX3A(x) => write!(f, "pitch := v{x:x}"),
+            Instruction::InsFX55(x) => write!(f, "save v{x:x}"),
+            Instruction::InsFX65(x) => write!(f, "load v{x:x}"),
+            Instruction::InsFX75(x) => write!(f, "saveflags v{x:x}"),
+            Instruction::InsFX85(x) => write!(f, "loadflags v{x:x}"),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+pub use listing::*;
+
+#[cfg(feature = "alloc")]
+mod listing {
+    use super::Syntax;
+    use crate::instruction::{Instruction, LONG_OPCODE_PREFIX};
+    use alloc::{collections::BTreeMap, vec::Vec};
+    use core::fmt;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum LabelKind {
+        Subroutine,
+        Jump,
+        Data,
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum LineKind {
+        Code(Instruction),
+        Data,
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct Line {
+        pub address: u16,
+        pub bytes: [u8; 4],
+        pub size: usize,
+        pub kind: LineKind,
+    }
+
+    pub struct Disassembly {
+        pub lines: Vec<Line>,
+        pub labels: BTreeMap<u16, LabelKind>,
+        pub syntax: Syntax,
+    }
+
+    struct Label(u16, LabelKind);
+
+    impl fmt::Display for Label {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let prefix = match self.1 {
+                LabelKind::Subroutine => "sub",
+                LabelKind::Jump => "label",
+                LabelKind::Data => "data",
+            };
+            write!(f, "{prefix}_{:03X}", self.0)
+        }
+    }
+
+    pub(crate) fn decode_at(rom: &[u8], offset: usize) -> Option<Instruction> {
+        let word_at = |offset: usize| {
+            Some(u16::from_be_bytes([
+                *rom.get(offset)?,
+                *rom.get(offset + 1)?,
+            ]))
+        };
+        let opcode = word_at(offset)?;
+        let next_opcode = if opcode == LONG_OPCODE_PREFIX {
+            word_at(offset + 2)?
+        } else {
+            0
+        };
+        Instruction::decode(opcode, next_opcode).ok()
+    }
+
+    fn target_label(instruction: &Instruction) -> Option<(u16, LabelKind)> {
+        match *instruction {
+            Instruction::Ins1NNN(nnn) => Some((nnn, LabelKind::Jump)),
+            Instruction::Ins2NNN(nnn) => Some((nnn, LabelKind::Subroutine)),
+            Instruction::InsANNN(nnn) => Some((nnn, LabelKind::Data)),
+            Instruction::InsF000(nnnn) => Some((nnnn, LabelKind::Data)),
+            _ => None,
+        }
+    }
+
+    // Linear sweep of a ROM loaded at program_start. Jump and call targets
+    // start code, I targets start data, and words that do not decode are
+    // data as well.
+    pub fn disassemble(rom: &[u8], program_start: u16, syntax: Syntax) -> Disassembly {
+        let base = program_start as usize;
+        let in_rom = |address: u16| (base..base + rom.len()).contains(&(address as usize));
+
+        let mut labels = BTreeMap::new();
+        for offset in (0..rom.len()).step_by(2) {
+            if let Some((address, kind)) = decode_at(rom, offset)
+                .as_ref()
+                .and_then(target_label)
+                .filter(|(address, _)| in_rom(*address))
+            {
+                let label = labels.entry(address).or_insert(kind);
+                // Code labels win over data labels for the same address
+                if *label == LabelKind::Data {
+                    *label = kind;
+                }
+            }
+        }
+        let is_code_label = |address: usize| {
+            matches!(
+                labels.get(&(address as u16)),
+                Some(LabelKind::Subroutine | LabelKind::Jump)
+            )
+        };
+
+        let mut lines = Vec::new();
+        let mut offset = 0;
+        let mut in_data = false;
+        while offset < rom.len() {
+            let address = base + offset;
+            match labels.get(&(address as u16)) {
+                Some(LabelKind::Data) => in_data = true,
+                Some(_) => in_data = false,
+                None => {}
+            }
+            let instruction = decode_at(rom, offset).filter(|instruction| {
+                // An instruction may not swallow the start of a code label
+                let size = instruction.size() as usize;
+                !(1..size).any(|index| is_code_label(address + index))
+            });
+            let (kind, size) = match instruction {
+                Some(instruction) if !in_data => {
+                    let size = instruction.size() as usize;
+                    (LineKind::Code(instruction), size)
+                }
+                // Undecodable words keep code aligned, data is listed bytewise
+                _ if !in_data && offset + 1 < rom.len() && !is_code_label(address + 1) => {
+                    (LineKind::Data, 2)
+                }
+                _ => (LineKind::Data, 1),
+            };
+            let mut bytes = [0; 4];
+            bytes[..size].copy_from_slice(&rom[offset..offset + size]);
+            lines.push(Line {
+                address: address as u16,
+                bytes,
+                size,
+                kind,
+            });
+            offset += size;
+        }
+
+        Disassembly {
+            lines,
+            labels,
+            syntax,
+        }
+    }
+
+    impl Disassembly {
+        fn label(&self, address: u16) -> Option<Label> {
+            self.labels.get(&address).map(|&kind| Label(address, kind))
+        }
+
+        fn write_instruction(
+            &self,
+            f: &mut fmt::Formatter<'_>,
+            instruction: &Instruction,
+        ) -> fmt::Result {
+            let label = target_label(instruction).and_then(|(address, _)| self.label(address));
+            let Some(label) = label else {
+                return match self.syntax {
+                    Syntax::Standard => write!(f, "{instruction}"),
+                    Syntax::Octo => write!(f, "{}", instruction.octo()),
+                };
+            };
+            let mnemonic = match (self.syntax, instruction) {
+                (Syntax::Standard, Instruction::Ins1NNN(_)) => "JP",
+                (Syntax::Standard, Instruction::Ins2NNN(_)) => "CALL",
+                (Syntax::Standard, _) => "LD I,",
+                (Syntax::Octo, Instruction::Ins1NNN(_)) => "jump",
+                (Syntax::Octo, Instruction::Ins2NNN(_)) => ":call",
+                (Syntax::Octo, Instruction::InsF000(_)) => "i := long",
+                (Syntax::Octo, _) => "i :=",
+            };
+            write!(f, "{mnemonic} {label}")
+        }
+    }
+
+    impl Disassembly {
+        // Writes the listing with a comment at the end of every line that
+        // annotate returns one for
+        pub fn write_annotated<'a, C, F>(
+            &'a self,
+            f: &mut fmt::Formatter<'_>,
+            mut annotate: F,
+        ) -> fmt::Result
+        where
+            C: fmt::Display,
+            F: FnMut(&'a Line) -> Option<C>,
+        {
+            for line in &self.lines {
+                if let Some(label) = self.label(line.address) {
+                    match self.syntax {
+                        Syntax::Standard => writeln!(f, "{label}:")?,
+                        Syntax::Octo => writeln!(f, ": {label}")?,
+                    }
+                }
+                let bytes = &line.bytes[..line.size];
+                match self.syntax {
+                    Syntax::Standard => {
+                        write!(f, "    0x{:03X}  ", line.address)?;
+                        for byte in bytes {
+                            write!(f, "{byte:02X}")?;
+                        }
+                        write!(f, "{:width$}  ", "", width = 8 - bytes.len() * 2)?;
+                        match &line.kind {
+                            LineKind::Code(instruction) => {
+                                self.write_instruction(f, instruction)?
+                            }
+                            LineKind::Data => {
+                                write!(f, "DB")?;
+                                for (index, byte) in bytes.iter().enumerate() {
+                                    let separator = if index == 0 { " " } else { ", " };
+                                    write!(f, "{separator}0x{byte:02X}")?;
+                                }
+                            }
+                        }
+                        if let Some(comment) = annotate(line) {
+                            write!(f, "  ; {comment}")?;
+                        }
+                        writeln!(f)?;
+                    }
+                    Syntax::Octo => {
+                        write!(f, "\t")?;
+                        match &line.kind {
+                            LineKind::Code(instruction) => {
+                                self.write_instruction(f, instruction)?
+                            }
+                            LineKind::Data => {
+                                for (index, byte) in bytes.iter().enumerate() {
+                                    let separator = if index == 0 { "" } else { " " };
+                                    write!(f, "{separator}0x{byte:02X}")?;
+                                }
+                            }
+                        }
+                        write!(f, " # 0x{:03X}", line.address)?;
+                        if let Some(comment) = annotate(line) {
+                            write!(f, " {comment}")?;
+                        }
+                        writeln!(f)?;
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl fmt::Display for Disassembly {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.write_annotated(f, |_| None::<&str>)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    struct Buffer {
+        bytes: [u8; 64],
+        len: usize,
+    }
+
+    impl Write for Buffer {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let end = self.len + s.len();
+            self.bytes
+                .get_mut(self.len..end)
+                .ok_or(fmt::Error)?
+                .copy_from_slice(s.as_bytes());
+            self.len = end;
+            Ok(())
+        }
+    }
+
+    fn render(args: fmt::Arguments) -> Buffer {
+        let mut buffer = Buffer {
+            bytes: [0; 64],
+            len: 0,
+        };
+        buffer.write_fmt(args).unwrap();
+        buffer
+    }
+
+    fn assert_renders(args: fmt::Arguments, expected: &str) {
+        let buffer = render(args);
+        assert_eq!(
+            core::str::from_utf8(&buffer.bytes[..buffer.len]).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn displays_standard_mnemonics() {
+        assert_renders(format_args!("{}", Instruction::Ins00E0), "CLS");
+        assert_renders(
+            format_args!("{}", Instruction::Ins2NNN(0x2A4)),
+            "CALL 0x2A4",
+        );
+        assert_renders(
+            format_args!("{}", Instruction::Ins6XNN(0xA, 0x05)),
+            "LD VA, 0x05",
+        );
+        assert_renders(
+            format_args!("{}", Instruction::InsDXYN(0x1, 0x2, 0xF)),
+            "DRW V1, V2, 15",
+        );
+        assert_renders(format_args!("{}", Instruction::InsFX65(0x3)), "LD V3, [I]");
+        assert_renders(
+            format_args!("{}", Instruction::InsF000(0x1234)),
+            "LD I, 0x1234",
+        );
+    }
+
+    #[test]
+    fn displays_octo_syntax() {
+        assert_renders(format_args!("{}", Instruction::Ins00EE.octo()), "return");
+        assert_renders(
+            format_args!("{}", Instruction::Ins3XNN(0x1, 0x20).octo()),
+            "if v1 != 0x20 then",
+        );
+        assert_renders(
+            format_args!("{}", Instruction::Ins8XY7(0xB, 0xC).octo()),
+            "vb =- vc",
+        );
+        assert_renders(
+            format_args!("{}", Instruction::InsEXA1(0x4).octo()),
+            "if v4 key then",
+        );
+        assert_renders(
+            format_args!("{}", Instruction::InsFX29(0x2).octo()),
+            "i := hex v2",
+        );
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod listing_tests {
+    use super::*;
+    use crate::chip8::PROGRAM_START;
+    use alloc::string::ToString;
+
+    // 0x200: 2206 (call), 1204 (jump to self), 0x204: 1204
+    // 0x206: A20C, D015, 00EE, 0x20C: F0 90 F0 (sprite)
+    const ROM: [u8; 15] = [
+        0x22, 0x06, 0x12, 0x04, 0x12, 0x04, 0xA2, 0x0C, 0xD0, 0x15, 0x00, 0xEE, 0xF0, 0x90, 0xF0,
+    ];
+
+    #[test]
+    fn marks_labels_and_data() {
+        let disassembly = disassemble(&ROM, PROGRAM_START, Syntax::Standard);
+        assert_eq!(disassembly.labels.get(&0x206), Some(&LabelKind::Subroutine));
+        assert_eq!(disassembly.labels.get(&0x204), Some(&LabelKind::Jump));
+        assert_eq!(disassembly.labels.get(&0x20C), Some(&LabelKind::Data));
+        let data_lines = disassembly
+            .lines
+            .iter()
+            .filter(|line| line.kind == LineKind::Data)
+            .count();
+        assert_eq!(data_lines, 3);
+        assert_eq!(
+            disassembly.lines[0].kind,
+            LineKind::Code(Instruction::Ins2NNN(0x206))
+        );
+    }
+
+    #[test]
+    fn addresses_follow_program_start() {
+        // 0x600: 2604 (call), 1602 (jump to self), 0x604: 00EE
+        let rom = [0x26, 0x04, 0x16, 0x02, 0x00, 0xEE];
+        let listing = disassemble(&rom, 0x600, Syntax::Standard).to_string();
+        assert!(listing.contains("    0x600  2604      CALL sub_604\n"));
+        assert!(listing.contains("label_602:\n    0x602  1602      JP label_602\n"));
+    }
+
+    #[test]
+    fn renders_listing_with_label_operands() {
+        let listing = disassemble(&ROM, PROGRAM_START, Syntax::Standard).to_string();
+        assert!(listing.contains("sub_206:\n"));
+        assert!(listing.contains("    0x200  2206      CALL sub_206\n"));
+        assert!(listing.contains("    0x206  A20C      LD I, data_20C\n"));
+        assert!(listing.contains("    0x20C  F0        DB 0xF0\n"));
+
+        let listing = disassemble(&ROM, PROGRAM_START, Syntax::Octo).to_string();
+        assert!(listing.contains(": label_204\n\tjump label_204 # 0x204\n"));
+        assert!(listing.contains("\tsprite v0 v1 5 # 0x208\n"));
+        assert!(listing.contains("\t0x90 # 0x20D\n"));
+    }
+}
@@ -0,0 +1,181 @@
+// Bit-packed frame buffer: one u128 per row and plane, with the leftmost pixel
+// in the most significant bit. Lores mode uses the top-left 64x32 pixels.
+use crate::chip8::{PLANE_COUNT, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+const _: () = assert!(SCREEN_WIDTH == u128::BITS as usize);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Display {
+    planes: [[u128; SCREEN_HEIGHT]; PLANE_COUNT],
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display {
+    pub const fn new() -> Self {
+        Self {
+            planes: [[0; SCREEN_HEIGHT]; PLANE_COUNT],
+        }
+    }
+
+    // Plane bits of a pixel, the first plane in bit 0
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        if x >= SCREEN_WIDTH || y >= SCREEN_HEIGHT {
+            return 0;
+        }
+        (0..PLANE_COUNT).fold(0, |pixel, plane| {
+            pixel | (((self.planes[plane][y] & column_bit(x) != 0) as u8) << plane)
+        })
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, pixel: u8) {
+        if x >= SCREEN_WIDTH || y >= SCREEN_HEIGHT {
+            return;
+        }
+        for (plane, rows) in self.planes.iter_mut().enumerate() {
+            if pixel & (1 << plane) != 0 {
+                rows[y] |= column_bit(x);
+            } else {
+                rows[y] &= !column_bit(x);
+            }
+        }
+    }
+
+    pub fn row(&self, plane: usize, y: usize) -> u128 {
+        self.planes[plane][y]
+    }
+
+    pub fn set_row(&mut self, plane: usize, y: usize, bits: u128) {
+        self.planes[plane][y] = bits;
+    }
+
+    pub fn is_blank(&self) -> bool {
+        self.planes.iter().flatten().all(|&row| row == 0)
+    }
+
+    pub(crate) fn clear(&mut self, planes: u8) {
+        for (_, rows) in self.selected(planes) {
+            rows.fill(0);
+        }
+    }
+
+    // Moves the selected planes by whole pixels within a width x height screen,
+    // pixels scrolled off the screen are lost
+    pub(crate) fn scroll(&mut self, planes: u8, width: usize, height: usize, dx: isize, dy: isize) {
+        let mask = visible_mask(width);
+        for (_, rows) in self.selected(planes) {
+            let source = *rows;
+            for (y, row) in rows.iter_mut().enumerate() {
+                let source_y = y as isize - dy;
+                *row = if y < height && (0..height as isize).contains(&source_y) {
+                    let bits = source[source_y as usize];
+                    let moved = if dx >= 0 { bits >> dx } else { bits << -dx };
+                    moved & mask
+                } else {
+                    0
+                };
+            }
+        }
+    }
+
+    // XORs a positioned sprite row into a plane, returns whether it erased a pixel
+    pub(crate) fn xor_row(&mut self, plane: usize, y: usize, bits: u128) -> bool {
+        let row = &mut self.planes[plane][y];
+        let collision = *row & bits != 0;
+        *row ^= bits;
+        collision
+    }
+
+    fn selected(
+        &mut self,
+        planes: u8,
+    ) -> impl Iterator<Item = (usize, &mut [u128; SCREEN_HEIGHT])> {
+        self.planes
+            .iter_mut()
+            .enumerate()
+            .filter(move |(plane, _)| planes & (1 << plane) != 0)
+    }
+}
+
+// Places a sprite row `sprite_width` pixels wide at column x of a screen
+// `screen_width` pixels wide. Returns the visible bits and the bits past the
+// right edge moved around to the left edge.
+pub(crate) fn place_row(
+    sprite: u16,
+    sprite_width: usize,
+    x: usize,
+    screen_width: usize,
+) -> (u128, u128) {
+    let aligned = (sprite as u128) << (u128::BITS as usize - sprite_width);
+    let shifted = aligned >> x;
+    let mask = visible_mask(screen_width);
+    // Pixels shifted out of the u128 and pixels beyond a narrower screen
+    let lost = if x == 0 {
+        0
+    } else {
+        aligned << (u128::BITS as usize - x)
+    };
+    let beyond = (shifted & !mask)
+        .checked_shl(screen_width as u32)
+        .unwrap_or(0);
+    (shifted & mask, lost | beyond)
+}
+
+fn visible_mask(width: usize) -> u128 {
+    !(u128::MAX.checked_shr(width as u32).unwrap_or(0))
+}
+
+fn column_bit(x: usize) -> u128 {
+    1 << (SCREEN_WIDTH - 1 - x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Display, place_row};
+
+    #[test]
+    fn pixels_map_to_row_bits() {
+        let mut display = Display::new();
+        display.set_pixel(0, 3, 0b01);
+        display.set_pixel(127, 3, 0b11);
+        assert_eq!(display.row(0, 3), 1 << 127 | 1);
+        assert_eq!(display.row(1, 3), 1);
+        assert_eq!(display.pixel(127, 3), 0b11);
+        assert_eq!(display.pixel(128, 3), 0);
+
+        display.clear(0b01);
+        assert_eq!(display.pixel(0, 3), 0);
+        assert_eq!(display.pixel(127, 3), 0b10);
+        assert!(!display.is_blank());
+    }
+
+    #[test]
+    fn places_rows_with_wrap_around() {
+        let (visible, wrapped) = place_row(0b1100_0000, 8, 63, 64);
+        assert_eq!(visible, 1 << 64);
+        assert_eq!(wrapped, 1 << 127);
+
+        let (visible, wrapped) = place_row(0x8001, 16, 120, 128);
+        assert_eq!(visible, 1 << 7);
+        assert_eq!(wrapped, 1 << (127 - 7));
+
+        assert_eq!(place_row(0xFF, 8, 0, 128), (0xFF << 120, 0));
+    }
+
+    #[test]
+    fn scrolls_within_the_screen() {
+        let mut display = Display::new();
+        display.set_pixel(62, 0, 0b01);
+        display.scroll(0b01, 64, 32, 4, 31);
+        assert!(display.is_blank());
+
+        display.set_pixel(10, 10, 0b11);
+        display.scroll(0b10, 128, 64, -4, 2);
+        assert_eq!(display.pixel(10, 10), 0b01);
+        assert_eq!(display.pixel(6, 12), 0b10);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::instruction::Instruction;
+
 #[derive(PartialEq, Eq)]
 pub enum InstructionError {
     UnknownOpcode(u16),
@@ -46,3 +48,303 @@ impl core::fmt::Display for InstructionError {
 }
 
 impl core::error::Error for InstructionError {}
+
+#[derive(PartialEq, Eq)]
+pub enum ConfigError {
+    InvalidMemorySize(usize),
+    ProgramStartOutOfBounds {
+        program_start: u16,
+        memory_size: usize,
+    },
+    FontsetOutOfBounds {
+        fontset_start: usize,
+        memory_size: usize,
+    },
+    FontsetOverlapsProgram {
+        fontset_start: usize,
+        program_start: u16,
+    },
+    MemoryExceedsCapacity {
+        memory_size: usize,
+        capacity: usize,
+    },
+}
+
+impl core::fmt::Debug for ConfigError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ConfigError::InvalidMemorySize(memory_size) => {
+                write!(f, "InvalidMemorySize(memory_size={memory_size})")
+            }
+            ConfigError::ProgramStartOutOfBounds {
+                program_start,
+                memory_size,
+            } => write!(
+                f,
+                "ProgramStartOutOfBounds(program_start=0x{program_start:04x}, memory_size={memory_size})"
+            ),
+            ConfigError::FontsetOutOfBounds {
+                fontset_start,
+                memory_size,
+            } => write!(
+                f,
+                "FontsetOutOfBounds(fontset_start=0x{fontset_start:04x}, memory_size={memory_size})"
+            ),
+            ConfigError::FontsetOverlapsProgram {
+                fontset_start,
+                program_start,
+            } => write!(
+                f,
+                "FontsetOverlapsProgram(fontset_start=0x{fontset_start:04x}, program_start=0x{program_start:04x})"
+            ),
+            ConfigError::MemoryExceedsCapacity {
+                memory_size,
+                capacity,
+            } => write!(
+                f,
+                "MemoryExceedsCapacity(memory_size={memory_size}, capacity={capacity})"
+            ),
+        }
+    }
+}
+
+impl core::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl core::error::Error for ConfigError {}
+
+#[derive(PartialEq, Eq)]
+pub enum FontsetError {
+    InvalidSize(usize),
+}
+
+impl core::fmt::Debug for FontsetError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FontsetError::InvalidSize(size) => write!(f, "InvalidSize(size={size})"),
+        }
+    }
+}
+
+impl core::fmt::Display for FontsetError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl core::error::Error for FontsetError {}
+
+pub const CALL_STACK_SNAPSHOT_SIZE: usize = 4;
+
+// An InstructionError raised by Chip8::tick, with the machine state at the
+// faulting instruction
+#[derive(PartialEq, Eq)]
+pub struct ExecutionError {
+    pub error: InstructionError,
+    pub program_counter: u16,
+    // The word at the program counter, None when it is outside memory
+    pub opcode: Option<u16>,
+    pub instruction: Option<Instruction>,
+    pub register_i: u16,
+    pub stack_pointer: u8,
+    // Innermost return address first
+    call_stack: [u16; CALL_STACK_SNAPSHOT_SIZE],
+}
+
+impl ExecutionError {
+    pub fn new(
+        error: InstructionError,
+        program_counter: u16,
+        opcode: Option<u16>,
+        instruction: Option<Instruction>,
+        register_i: u16,
+        stack: &[u16],
+    ) -> Self {
+        let mut call_stack = [0; CALL_STACK_SNAPSHOT_SIZE];
+        for (slot, &address) in call_stack.iter_mut().zip(stack.iter().rev()) {
+            *slot = address;
+        }
+        Self {
+            error,
+            program_counter,
+            opcode,
+            instruction,
+            register_i,
+            stack_pointer: stack.len() as u8,
+            call_stack,
+        }
+    }
+
+    // The innermost CALL_STACK_SNAPSHOT_SIZE return addresses at most
+    pub fn call_stack(&self) -> &[u16] {
+        &self.call_stack[..(self.stack_pointer as usize).min(CALL_STACK_SNAPSHOT_SIZE)]
+    }
+}
+
+impl core::fmt::Debug for ExecutionError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "ExecutionError(error={:?}, pc=0x{:04x}, opcode={:04x?}, instruction={:?}, \
+             i=0x{:04x}, sp={}, call_stack={:04x?})",
+            self.error,
+            self.program_counter,
+            self.opcode,
+            self.instruction,
+            self.register_i,
+            self.stack_pointer,
+            self.call_stack()
+        )
+    }
+}
+
+// Reads as one line for crash reports, e.g.
+// `StackOverflow at PC 0x0208: 2208 (CALL 0x208), I 0x0000, SP 16, call stack 0x020A 0x020A ...`
+impl core::fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?} at PC 0x{:04X}: ", self.error, self.program_counter)?;
+        match self.opcode {
+            Some(opcode) => write!(f, "{opcode:04X}")?,
+            None => write!(f, "----")?,
+        }
+        if let Some(instruction) = &self.instruction {
+            write!(f, " ({instruction})")?;
+        }
+        write!(
+            f,
+            ", I 0x{:04X}, SP {}, call stack",
+            self.register_i, self.stack_pointer
+        )?;
+        if self.stack_pointer == 0 {
+            write!(f, " empty")?;
+        }
+        for address in self.call_stack() {
+            write!(f, " 0x{address:04X}")?;
+        }
+        if self.stack_pointer as usize > CALL_STACK_SNAPSHOT_SIZE {
+            write!(f, " ...")?;
+        }
+        Ok(())
+    }
+}
+
+impl core::error::Error for ExecutionError {}
+
+#[derive(PartialEq, Eq)]
+pub enum SnapshotError {
+    BufferTooSmall { required: usize, available: usize },
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    InvalidConfig(ConfigError),
+    InvalidLength { expected: usize, actual: usize },
+    InvalidStackPointer(u8),
+    InvalidKeyIndex(u8),
+    InvalidRegisterIndex(u8),
+}
+
+impl core::fmt::Debug for SnapshotError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SnapshotError::BufferTooSmall {
+                required,
+                available,
+            } => {
+                write!(
+                    f,
+                    "BufferTooSmall(required={required}, available={available})"
+                )
+            }
+            SnapshotError::InvalidMagic => write!(f, "InvalidMagic"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "UnsupportedVersion(version={version})")
+            }
+            SnapshotError::InvalidConfig(error) => write!(f, "InvalidConfig({error:?})"),
+            SnapshotError::InvalidLength { expected, actual } => {
+                write!(f, "InvalidLength(expected={expected}, actual={actual})")
+            }
+            SnapshotError::InvalidStackPointer(stack_pointer) => {
+                write!(f, "InvalidStackPointer(sp={stack_pointer})")
+            }
+            SnapshotError::InvalidKeyIndex(key) => write!(f, "InvalidKeyIndex(key={key})"),
+            SnapshotError::InvalidRegisterIndex(register) => {
+                write!(f, "InvalidRegisterIndex(register={register})")
+            }
+        }
+    }
+}
+
+impl core::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl core::error::Error for SnapshotError {}
+
+#[derive(PartialEq, Eq)]
+pub enum MovieError {
+    Truncated { required: usize, available: usize },
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    PartialFrame(usize),
+}
+
+impl core::fmt::Debug for MovieError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MovieError::Truncated {
+                required,
+                available,
+            } => {
+                write!(f, "Truncated(required={required}, available={available})")
+            }
+            MovieError::InvalidMagic => write!(f, "InvalidMagic"),
+            MovieError::UnsupportedVersion(version) => {
+                write!(f, "UnsupportedVersion(version={version})")
+            }
+            MovieError::PartialFrame(frame) => write!(f, "PartialFrame(frame={frame})"),
+        }
+    }
+}
+
+impl core::fmt::Display for MovieError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl core::error::Error for MovieError {}
+
+#[derive(PartialEq, Eq)]
+pub enum DebuggerError {
+    TooManyBreakpoints(usize),
+    TooManyWatchpoints(usize),
+    InvalidWatchRange { start: u16, end: u16 },
+    InvalidRegister(u8),
+}
+
+impl core::fmt::Debug for DebuggerError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DebuggerError::TooManyBreakpoints(max) => write!(f, "TooManyBreakpoints(max={max})"),
+            DebuggerError::TooManyWatchpoints(max) => write!(f, "TooManyWatchpoints(max={max})"),
+            DebuggerError::InvalidWatchRange { start, end } => {
+                write!(f, "InvalidWatchRange(start=0x{start:04x}, end=0x{end:04x})")
+            }
+            DebuggerError::InvalidRegister(register) => {
+                write!(f, "InvalidRegister(register={register})")
+            }
+        }
+    }
+}
+
+impl core::fmt::Display for DebuggerError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl core::error::Error for DebuggerError {}
@@ -0,0 +1,229 @@
+use crate::{
+    chip8::Chip8, error::ExecutionError, idle::RunState, rng::RandomSource, timing::VipTiming,
+};
+
+// How many instructions run_frame executes between two timer ticks
+#[derive(Debug, Clone)]
+pub enum FramePacing {
+    Ticks(usize),
+    // The COSMAC VIP cycle budget, overruns carry into the next frame
+    VipTiming(VipTiming),
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameConfig {
+    pub pacing: FramePacing,
+    // End the frame early once the program spins in an idle loop, the rest
+    // of it would repeat the loop without effect. Never done for
+    // run_frame_with, its observer expects every instruction.
+    pub skip_idle_loops: bool,
+}
+
+impl FrameConfig {
+    pub fn new(pacing: FramePacing) -> Self {
+        FrameConfig {
+            pacing,
+            skip_idle_loops: false,
+        }
+    }
+}
+
+// What happened during one 60 Hz frame, for frontends to act on
+#[derive(Debug, PartialEq, Eq)]
+pub struct FrameReport {
+    pub executed: usize,
+    // Redraw the screen
+    pub screen_changed: bool,
+    // Sound is on while the sound timer is non-zero at the end of a frame
+    pub sound_on: bool,
+    pub sound_started: bool,
+    pub sound_stopped: bool,
+    // Running unless the frame ended blocked on FX0A, in an idle loop, or
+    // halted
+    pub state: RunState,
+    // The frame stopped at this error and the timers did not tick
+    pub error: Option<ExecutionError>,
+}
+
+impl<R, const CAPACITY: usize> Chip8<R, CAPACITY>
+where
+    R: RandomSource,
+{
+    // Runs instructions up to the next vertical blank, then ticks the timers
+    pub fn run_frame(&mut self, config: &mut FrameConfig) -> FrameReport {
+        self.run_frame_observed(config, None::<fn(&Self)>)
+    }
+
+    // Like run_frame, calling before_tick ahead of every instruction, e.g. to
+    // trace it
+    pub fn run_frame_with<F>(&mut self, config: &mut FrameConfig, before_tick: F) -> FrameReport
+    where
+        F: FnMut(&Self),
+    {
+        self.run_frame_observed(config, Some(before_tick))
+    }
+
+    fn run_frame_observed<F>(
+        &mut self,
+        config: &mut FrameConfig,
+        mut before_tick: Option<F>,
+    ) -> FrameReport
+    where
+        F: FnMut(&Self),
+    {
+        let sound_was_on = self.sound_timer > 0;
+        if let FramePacing::VipTiming(timing) = &mut config.pacing {
+            timing.start_frame();
+        }
+        let skip_idle_loops = config.skip_idle_loops && before_tick.is_none();
+
+        let mut executed = 0;
+        let mut idle = None;
+        // The last loop head jumped back to, and whether it was checked
+        let mut loop_head = None;
+        let error = loop {
+            if self.exited || self.wait_for_vblank || self.is_waiting_for_key() {
+                break None;
+            }
+            match &mut config.pacing {
+                FramePacing::Ticks(ticks) if executed == *ticks => break None,
+                FramePacing::Ticks(_) => {}
+                FramePacing::VipTiming(timing) if !timing.has_cycles() => break None,
+                FramePacing::VipTiming(timing) => {
+                    if let Err(error) = timing.spend(self) {
+                        break Some(error);
+                    }
+                }
+            }
+            if let Some(before_tick) = &mut before_tick {
+                before_tick(self);
+            }
+            let program_counter = self.program_counter;
+            if let Err(error) = self.tick() {
+                break Some(error);
+            }
+            executed += 1;
+            // Loops only start over on a jump backwards. A loop is checked
+            // once per frame, the second time round, when registers it loads
+            // hold what the last pass left in them.
+            if skip_idle_loops && self.program_counter <= program_counter {
+                match loop_head {
+                    Some((head, false)) if head == self.program_counter => {
+                        idle = self.idle_loop();
+                        if idle.is_some() {
+                            break None;
+                        }
+                        loop_head = Some((head, true));
+                    }
+                    Some((head, true)) if head == self.program_counter => {}
+                    _ => loop_head = Some((self.program_counter, false)),
+                }
+            }
+        };
+
+        // Before the timers tick, a loop on FX07 still reads the value its
+        // last pass did
+        let state = idle.unwrap_or_else(|| self.run_state());
+        if error.is_none() {
+            self.tick_timer();
+        }
+        let sound_on = self.sound_timer > 0;
+        FrameReport {
+            executed,
+            screen_changed: core::mem::take(&mut self.draw_flag),
+            sound_on,
+            sound_started: sound_on && !sound_was_on,
+            sound_stopped: sound_was_on && !sound_on,
+            state,
+            error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::InstructionError, quirks::Quirks, rng::FixedSequence};
+
+    fn new_chip8(rom: &[u8]) -> Chip8<FixedSequence<'static>> {
+        let mut chip8 = Chip8::with_quirks(FixedSequence::new(&[0]), Quirks::CHIP48);
+        chip8.load_rom(rom).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn reports_screen_and_sound_changes() {
+        // 00E0, 6002, F018, 7101, 1206
+        let mut chip8 = new_chip8(&[0x00, 0xE0, 0x60, 0x02, 0xF0, 0x18, 0x71, 0x01, 0x12, 0x06]);
+        let mut config = FrameConfig::new(FramePacing::Ticks(8));
+
+        let report = chip8.run_frame(&mut config);
+        assert_eq!(report.executed, 8);
+        assert!(report.screen_changed && report.sound_on && report.sound_started);
+        assert!(!chip8.draw_flag);
+
+        let report = chip8.run_frame(&mut config);
+        assert!(!report.screen_changed && !report.sound_on && report.sound_stopped);
+    }
+
+    #[test]
+    fn stops_early_when_blocked_halted_or_failing() {
+        // F30A
+        let mut chip8 = new_chip8(&[0xF3, 0x0A]);
+        let report = chip8.run_frame(&mut FrameConfig::new(FramePacing::Ticks(8)));
+        assert_eq!(report.executed, 1);
+        assert_eq!(report.state, RunState::WaitingForKey);
+
+        // 00FD
+        let mut chip8 = new_chip8(&[0x00, 0xFD]);
+        let report = chip8.run_frame(&mut FrameConfig::new(FramePacing::Ticks(8)));
+        assert_eq!(report.executed, 1);
+        assert_eq!(report.state, RunState::Halted);
+
+        // 6001, 00EE
+        let mut chip8 = new_chip8(&[0x60, 0x01, 0x00, 0xEE]);
+        chip8.delay_timer = 5;
+        let report = chip8.run_frame(&mut FrameConfig::new(FramePacing::VipTiming(
+            VipTiming::new(),
+        )));
+        assert_eq!(report.executed, 1);
+        assert_eq!(
+            report.error.map(|error| error.error),
+            Some(InstructionError::StackUnderflow)
+        );
+        assert_eq!(chip8.delay_timer, 5);
+    }
+
+    #[test]
+    fn skips_idle_loops_only_when_asked_and_unobserved() {
+        let mut skipping = FrameConfig {
+            skip_idle_loops: true,
+            ..FrameConfig::new(FramePacing::Ticks(8))
+        };
+
+        // 1200
+        let mut chip8 = new_chip8(&[0x12, 0x00]);
+        let report = chip8.run_frame(&mut FrameConfig::new(FramePacing::Ticks(8)));
+        assert_eq!(report.executed, 8);
+        assert_eq!(report.state, RunState::Halted);
+        let report = chip8.run_frame(&mut skipping);
+        assert_eq!(report.executed, 2);
+        assert_eq!(report.state, RunState::Halted);
+
+        // F007, 3000, 1200
+        let mut chip8 = new_chip8(&[0xF0, 0x07, 0x30, 0x00, 0x12, 0x00]);
+        chip8.delay_timer = 3;
+        let report = chip8.run_frame(&mut FrameConfig::new(FramePacing::Ticks(8)));
+        assert_eq!(report.executed, 8);
+        assert_eq!(report.state, RunState::Idle);
+        let report = chip8.run_frame(&mut skipping);
+        assert_eq!(report.executed, 4);
+        assert_eq!(report.state, RunState::Idle);
+        assert_eq!(chip8.delay_timer, 1);
+
+        // An observer sees every instruction of the frame
+        let mut observed = 0;
+        let report = chip8.run_frame_with(&mut skipping, |_| observed += 1);
+        assert_eq!((report.executed, observed), (8, 8));
+    }
+}
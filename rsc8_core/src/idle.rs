@@ -0,0 +1,170 @@
+use crate::{
+    chip8::{Chip8, KEYPAD_SIZE},
+    instruction::{Instruction, LONG_OPCODE_PREFIX},
+    rng::RandomSource,
+};
+
+// Longest loop body the detector follows
+const MAX_LOOP_INSTRUCTIONS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunState {
+    Running,
+    // Blocked on FX0A until a key is pressed and released
+    WaitingForKey,
+    // Spinning in a loop that only the delay timer or the keypad can end,
+    // nothing happens until one of them changes
+    Idle,
+    // Exited through 00FD, or stuck in a loop nothing can end, like a 1NNN
+    // jumping to itself
+    Halted,
+}
+
+impl<R, const CAPACITY: usize> Chip8<R, CAPACITY>
+where
+    R: RandomSource,
+{
+    pub fn run_state(&self) -> RunState {
+        if self.exited {
+            RunState::Halted
+        } else if self.is_waiting_for_key() {
+            RunState::WaitingForKey
+        } else {
+            self.idle_loop().unwrap_or(RunState::Running)
+        }
+    }
+
+    // Follows the program from the program counter without executing it. A
+    // loop made only of jumps, skips, register loads, FX07 and key checks,
+    // that comes back to the program counter with the registers unchanged,
+    // repeats the same way until the delay timer or the keypad changes.
+    pub(crate) fn idle_loop(&self) -> Option<RunState> {
+        let start = self.program_counter;
+        let mut pc = start;
+        let mut v = self.register_v;
+        let mut i = self.register_i;
+        let mut reads_timer = false;
+        let mut reads_keys = false;
+        for _ in 0..MAX_LOOP_INSTRUCTIONS {
+            let instruction = self.instruction_at(pc).ok()?;
+            let next = pc.checked_add(instruction.size())?;
+            let skip = |taken: bool| {
+                if !taken {
+                    return Some(next);
+                }
+                // Same as skip_next_instruction, F000 NNNN is skipped whole
+                let address = next as usize;
+                let next_is_long = address + 1 < self.config.memory_size
+                    && u16::from_be_bytes([self.memory[address], self.memory[address + 1]])
+                        == LONG_OPCODE_PREFIX;
+                next.checked_add(if next_is_long { 4 } else { 2 })
+            };
+            let key = |x: u8| {
+                let key = v[x as usize] as usize;
+                (key < KEYPAD_SIZE).then(|| self.keypad[key])
+            };
+            pc = match instruction {
+                Instruction::Ins1NNN(nnn) => nnn,
+                Instruction::Ins3XNN(x, nn) => skip(v[x as usize] == nn)?,
+                Instruction::Ins4XNN(x, nn) => skip(v[x as usize] != nn)?,
+                Instruction::Ins5XY0(x, y) => skip(v[x as usize] == v[y as usize])?,
+                Instruction::Ins9XY0(x, y) => skip(v[x as usize] != v[y as usize])?,
+                Instruction::Ins6XNN(x, nn) => {
+                    v[x as usize] = nn;
+                    next
+                }
+                Instruction::Ins8XY0(x, y) => {
+                    v[x as usize] = v[y as usize];
+                    next
+                }
+                Instruction::InsANNN(nnn) => {
+                    i = nnn;
+                    next
+                }
+                Instruction::InsFX07(x) => {
+                    reads_timer = true;
+                    v[x as usize] = self.delay_timer;
+                    next
+                }
+                Instruction::InsEX9E(x) => {
+                    reads_keys = true;
+                    skip(key(x)?)?
+                }
+                Instruction::InsEXA1(x) => {
+                    reads_keys = true;
+                    skip(!key(x)?)?
+                }
+                _ => return None,
+            };
+            if pc == start {
+                if v != self.register_v || i != self.register_i {
+                    return None;
+                }
+                // A delay timer at zero stays there
+                let can_wake = reads_keys || (reads_timer && self.delay_timer > 0);
+                return Some(if can_wake {
+                    RunState::Idle
+                } else {
+                    RunState::Halted
+                });
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{quirks::Quirks, rng::FixedSequence};
+
+    fn new_chip8(rom: &[u8]) -> Chip8<FixedSequence<'static>> {
+        let mut chip8 = Chip8::with_quirks(FixedSequence::new(&[0]), Quirks::CHIP48);
+        chip8.load_rom(rom).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn self_jump_halts() {
+        // 1200
+        let chip8 = new_chip8(&[0x12, 0x00]);
+        assert_eq!(chip8.run_state(), RunState::Halted);
+        // 7001, 1200
+        let chip8 = new_chip8(&[0x70, 0x01, 0x12, 0x00]);
+        assert_eq!(chip8.run_state(), RunState::Running);
+    }
+
+    #[test]
+    fn delay_timer_wait_is_idle_until_it_expires() {
+        // F007, 3000, 1200, 6105
+        let mut chip8 = new_chip8(&[0xF0, 0x07, 0x30, 0x00, 0x12, 0x00, 0x61, 0x05]);
+        chip8.delay_timer = 3;
+        chip8.register_v[0] = 3;
+        assert_eq!(chip8.run_state(), RunState::Idle);
+
+        // V0 no longer matches what F007 reads, so the loop makes progress
+        chip8.tick_timer();
+        assert_eq!(chip8.run_state(), RunState::Running);
+
+        chip8.delay_timer = 0;
+        chip8.register_v[0] = 0;
+        assert_eq!(chip8.run_state(), RunState::Running);
+    }
+
+    #[test]
+    fn waiting_for_a_timer_that_never_starts_halts() {
+        // F007, 4000, 1200
+        let chip8 = new_chip8(&[0xF0, 0x07, 0x40, 0x00, 0x12, 0x00]);
+        assert_eq!(chip8.run_state(), RunState::Halted);
+    }
+
+    #[test]
+    fn key_polling_loop_is_idle() {
+        // 6005, E09E, 1202
+        let mut chip8 = new_chip8(&[0x60, 0x05, 0xE0, 0x9E, 0x12, 0x02]);
+        chip8.tick().unwrap();
+        assert_eq!(chip8.run_state(), RunState::Idle);
+        chip8.key_down(5);
+        assert_eq!(chip8.run_state(), RunState::Running);
+    }
+}
@@ -1,14 +1,26 @@
 use crate::error::InstructionError;
 
-#[derive(Debug, PartialEq, Eq)]
+// F000 NNNN is the only instruction that spans two words
+pub const LONG_OPCODE_PREFIX: u16 = 0xF000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Instruction {
+    Ins00CN(u8),
+    Ins00DN(u8),
     Ins00E0,
     Ins00EE,
+    Ins00FB,
+    Ins00FC,
+    Ins00FD,
+    Ins00FE,
+    Ins00FF,
     Ins1NNN(u16),
     Ins2NNN(u16),
     Ins3XNN(u8, u8),
     Ins4XNN(u8, u8),
     Ins5XY0(u8, u8),
+    Ins5XY2(u8, u8),
+    Ins5XY3(u8, u8),
     Ins6XNN(u8, u8),
     Ins7XNN(u8, u8),
     Ins8XY0(u8, u8),
@@ -27,15 +39,39 @@ pub enum Instruction {
     InsDXYN(u8, u8, u8),
     InsEX9E(u8),
     InsEXA1(u8),
+    InsF000(u16),
+    InsFN01(u8),
+    InsF002,
     InsFX07(u8),
     InsFX0A(u8),
     InsFX15(u8),
     InsFX18(u8),
     InsFX1E(u8),
     InsFX29(u8),
+    InsFX30(u8),
     InsFX33(u8),
+    InsFX3A(u8),
     InsFX55(u8),
     InsFX65(u8),
+    InsFX75(u8),
+    InsFX85(u8),
+}
+
+impl Instruction {
+    pub fn decode(opcode: u16, next_opcode: u16) -> Result<Instruction, InstructionError> {
+        match opcode {
+            // F000 NNNN: I = NNNN
+            LONG_OPCODE_PREFIX => Ok(Instruction::InsF000(next_opcode)),
+            _ => Instruction::try_from(opcode),
+        }
+    }
+
+    pub fn size(&self) -> u16 {
+        match self {
+            Instruction::InsF000(_) => 4,
+            _ => 2,
+        }
+    }
 }
 
 impl TryFrom<u16> for Instruction {
@@ -51,10 +87,24 @@ impl TryFrom<u16> for Instruction {
 
         match opcode & 0xF000 {
             0x0000 => match opcode {
+                // 00CN: Scroll screen down N pixels
+                0x00C0..=0x00CF => Ok(Instruction::Ins00CN(n)),
+                // 00DN: Scroll screen up N pixels
+                0x00D0..=0x00DF => Ok(Instruction::Ins00DN(n)),
                 // 00E0: Clear screen
                 0x00E0 => Ok(Instruction::Ins00E0),
                 // 00EE: Return from subroutine
                 0x00EE => Ok(Instruction::Ins00EE),
+                // 00FB: Scroll screen right 4 pixels
+                0x00FB => Ok(Instruction::Ins00FB),
+                // 00FC: Scroll screen left 4 pixels
+                0x00FC => Ok(Instruction::Ins00FC),
+                // 00FD: Exit interpreter
+                0x00FD => Ok(Instruction::Ins00FD),
+                // 00FE: Switch to low resolution (64x32)
+                0x00FE => Ok(Instruction::Ins00FE),
+                // 00FF: Switch to high resolution (128x64)
+                0x00FF => Ok(Instruction::Ins00FF),
                 _ => Err(InstructionError::UnknownOpcode(opcode)),
             },
             // 1NNN: Jump to NNN
@@ -65,9 +115,13 @@ impl TryFrom<u16> for Instruction {
             0x3000 => Ok(Instruction::Ins3XNN(x, nn)),
             // 4XNN: Skip next instruction if VX != NN
             0x4000 => Ok(Instruction::Ins4XNN(x, nn)),
-            // 5XY0: Skip next instruction if VX == VY
             0x5000 => match n {
+                // 5XY0: Skip next instruction if VX == VY
                 0x0 => Ok(Instruction::Ins5XY0(x, y)),
+                // 5XY2: Store VX..VY in memory starting at I
+                0x2 => Ok(Instruction::Ins5XY2(x, y)),
+                // 5XY3: Load VX..VY from memory starting at I
+                0x3 => Ok(Instruction::Ins5XY3(x, y)),
                 _ => Err(InstructionError::UnknownOpcode(opcode)),
             },
             // 6XNN: VX = NN
@@ -107,6 +161,7 @@ impl TryFrom<u16> for Instruction {
             // CXNN: VX = rand() & NN
             0xC000 => Ok(Instruction::InsCXNN(x, nn)),
             // DXYN: Draw an 8-pixel-wide, N-byte sprite at (VX, VY)
+            // DXY0: Draw a 16x16 sprite at (VX, VY)
             0xD000 => Ok(Instruction::InsDXYN(x, y, n)),
             0xE000 => match nn {
                 // EX9E: Skip next instruction if keypad[VX] is pressed
@@ -116,6 +171,11 @@ impl TryFrom<u16> for Instruction {
                 _ => Err(InstructionError::UnknownOpcode(opcode)),
             },
             0xF000 => match nn {
+                // F000 NNNN needs the following word, see Instruction::decode
+                // FN01: Select drawing planes N
+                0x01 => Ok(Instruction::InsFN01(x)),
+                // F002: Load 16-byte audio pattern from memory starting at I
+                0x02 if x == 0 => Ok(Instruction::InsF002),
                 // FX07: VX = delay_timer
                 0x07 => Ok(Instruction::InsFX07(x)),
                 // FX0A: Wait for key press, store key in VX
@@ -128,12 +188,20 @@ impl TryFrom<u16> for Instruction {
                 0x1E => Ok(Instruction::InsFX1E(x)),
                 // FX29: I = address of font in VX
                 0x29 => Ok(Instruction::InsFX29(x)),
+                // FX30: I = address of big font in VX
+                0x30 => Ok(Instruction::InsFX30(x)),
                 // FX33: Binary-coded decimal representation of VX
                 0x33 => Ok(Instruction::InsFX33(x)),
+                // FX3A: pitch = VX
+                0x3A => Ok(Instruction::InsFX3A(x)),
                 // FX55: Store V0..VX in memory starting at I
                 0x55 => Ok(Instruction::InsFX55(x)),
                 // FX65: Load V0..VX from memory starting at I
                 0x65 => Ok(Instruction::InsFX65(x)),
+                // FX75: Store V0..VX in RPL user flags
+                0x75 => Ok(Instruction::InsFX75(x)),
+                // FX85: Load V0..VX from RPL user flags
+                0x85 => Ok(Instruction::InsFX85(x)),
                 _ => Err(InstructionError::UnknownOpcode(opcode)),
             },
             _ => Err(InstructionError::UnknownOpcode(opcode)),
@@ -141,9 +209,75 @@ impl TryFrom<u16> for Instruction {
     }
 }
 
+// F000 NNNN encodes to its first word only, NNNN follows as a second word
+impl From<&Instruction> for u16 {
+    fn from(instruction: &Instruction) -> u16 {
+        let xy = |prefix: u16, x: u8, y: u8, n: u16| prefix | (x as u16) << 8 | (y as u16) << 4 | n;
+        let xnn = |prefix: u16, x: u8, nn: u8| prefix | (x as u16) << 8 | nn as u16;
+        match *instruction {
+            Instruction::Ins00CN(n) => 0x00C0 | n as u16,
+            Instruction::Ins00DN(n) => 0x00D0 | n as u16,
+            Instruction::Ins00E0 => 0x00E0,
+            Instruction::Ins00EE => 0x00EE,
+            Instruction::Ins00FB => 0x00FB,
+            Instruction::Ins00FC => 0x00FC,
+            Instruction::Ins00FD => 0x00FD,
+            Instruction::Ins00FE => 0x00FE,
+            Instruction::Ins00FF => 0x00FF,
+            Instruction::Ins1NNN(nnn) => 0x1000 | nnn,
+            Instruction::Ins2NNN(nnn) => 0x2000 | nnn,
+            Instruction::Ins3XNN(x, nn) => xnn(0x3000, x, nn),
+            Instruction::Ins4XNN(x, nn) => xnn(0x4000, x, nn),
+            Instruction::Ins5XY0(x, y) => xy(0x5000, x, y, 0x0),
+            Instruction::Ins5XY2(x, y) => xy(0x5000, x, y, 0x2),
+            Instruction::Ins5XY3(x, y) => xy(0x5000, x, y, 0x3),
+            Instruction::Ins6XNN(x, nn) => xnn(0x6000, x, nn),
+            Instruction::Ins7XNN(x, nn) => xnn(0x7000, x, nn),
+            Instruction::Ins8XY0(x, y) => xy(0x8000, x, y, 0x0),
+            Instruction::Ins8XY1(x, y) => xy(0x8000, x, y, 0x1),
+            Instruction::Ins8XY2(x, y) => xy(0x8000, x, y, 0x2),
+            Instruction::Ins8XY3(x, y) => xy(0x8000, x, y, 0x3),
+            Instruction::Ins8XY4(x, y) => xy(0x8000, x, y, 0x4),
+            Instruction::Ins8XY5(x, y) => xy(0x8000, x, y, 0x5),
+            Instruction::Ins8XY6(x, y) => xy(0x8000, x, y, 0x6),
+            Instruction::Ins8XY7(x, y) => xy(0x8000, x, y, 0x7),
+            Instruction::Ins8XYE(x, y) => xy(0x8000, x, y, 0xE),
+            Instruction::Ins9XY0(x, y) => xy(0x9000, x, y, 0x0),
+            Instruction::InsANNN(nnn) => 0xA000 | nnn,
+            Instruction::InsBNNN(nnn) => 0xB000 | nnn,
+            Instruction::InsCXNN(x, nn) => xnn(0xC000, x, nn),
+            Instruction::InsDXYN(x, y, n) => xy(0xD000, x, y, n as u16),
+            Instruction::InsEX9E(x) => xnn(0xE000, x, 0x9E),
+            Instruction::InsEXA1(x) => xnn(0xE000, x, 0xA1),
+            Instruction::InsF000(_) => LONG_OPCODE_PREFIX,
+            Instruction::InsFN01(n) => xnn(0xF000, n, 0x01),
+            Instruction::InsF002 => 0xF002,
+            Instruction::InsFX07(x) => xnn(0xF000, x, 0x07),
+            Instruction::InsFX0A(x) => xnn(0xF000, x, 0x0A),
+            Instruction::InsFX15(x) => xnn(0xF000, x, 0x15),
+            Instruction::InsFX18(x) => xnn(0xF000, x, 0x18),
+            Instruction::InsFX1E(x) => xnn(0xF000, x, 0x1E),
+            Instruction::InsFX29(x) => xnn(0xF000, x, 0x29),
+            Instruction::InsFX30(x) => xnn(0xF000, x, 0x30),
+            Instruction::InsFX33(x) => xnn(0xF000, x, 0x33),
+            Instruction::InsFX3A(x) => xnn(0xF000, x, 0x3A),
+            Instruction::InsFX55(x) => xnn(0xF000, x, 0x55),
+            Instruction::InsFX65(x) => xnn(0xF000, x, 0x65),
+            Instruction::InsFX75(x) => xnn(0xF000, x, 0x75),
+            Instruction::InsFX85(x) => xnn(0xF000, x, 0x85),
+        }
+    }
+}
+
+impl From<Instruction> for u16 {
+    fn from(instruction: Instruction) -> u16 {
+        u16::from(&instruction)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Instruction;
+    use super::{Instruction, LONG_OPCODE_PREFIX};
     use crate::error::InstructionError;
 
     #[test]
@@ -166,10 +300,54 @@ mod tests {
         assert_eq!(Instruction::try_from(0xFB65), Ok(Instruction::InsFX65(0xB)));
     }
 
+    #[test]
+    fn decodes_super_chip_opcodes() {
+        assert_eq!(Instruction::try_from(0x00C5), Ok(Instruction::Ins00CN(0x5)));
+        assert_eq!(Instruction::try_from(0x00FB), Ok(Instruction::Ins00FB));
+        assert_eq!(Instruction::try_from(0x00FC), Ok(Instruction::Ins00FC));
+        assert_eq!(Instruction::try_from(0x00FD), Ok(Instruction::Ins00FD));
+        assert_eq!(Instruction::try_from(0x00FE), Ok(Instruction::Ins00FE));
+        assert_eq!(Instruction::try_from(0x00FF), Ok(Instruction::Ins00FF));
+        assert_eq!(
+            Instruction::try_from(0xD120),
+            Ok(Instruction::InsDXYN(0x1, 0x2, 0x0))
+        );
+        assert_eq!(Instruction::try_from(0xF330), Ok(Instruction::InsFX30(0x3)));
+        assert_eq!(Instruction::try_from(0xF775), Ok(Instruction::InsFX75(0x7)));
+        assert_eq!(Instruction::try_from(0xF785), Ok(Instruction::InsFX85(0x7)));
+    }
+
+    #[test]
+    fn decodes_xo_chip_opcodes() {
+        assert_eq!(Instruction::try_from(0x00D4), Ok(Instruction::Ins00DN(0x4)));
+        assert_eq!(
+            Instruction::try_from(0x5122),
+            Ok(Instruction::Ins5XY2(0x1, 0x2))
+        );
+        assert_eq!(
+            Instruction::try_from(0x5123),
+            Ok(Instruction::Ins5XY3(0x1, 0x2))
+        );
+        assert_eq!(Instruction::try_from(0xF301), Ok(Instruction::InsFN01(0x3)));
+        assert_eq!(Instruction::try_from(0xF002), Ok(Instruction::InsF002));
+        assert_eq!(Instruction::try_from(0xF53A), Ok(Instruction::InsFX3A(0x5)));
+        assert_eq!(
+            Instruction::decode(0xF000, 0x1234),
+            Ok(Instruction::InsF000(0x1234))
+        );
+        assert_eq!(
+            Instruction::decode(0x6012, 0x1234),
+            Ok(Instruction::Ins6XNN(0x0, 0x12))
+        );
+        assert_eq!(Instruction::InsF000(0).size(), 4);
+        assert_eq!(Instruction::Ins00E0.size(), 2);
+    }
+
     #[test]
     fn rejects_opcode_prefixes_that_only_partially_match() {
         for opcode in [
-            0x0010, 0x00FE, 0x5121, 0x9234, 0xE490, 0xEAAE, 0xF22A, 0xF334, 0xFA5A, 0xFB6A,
+            0x0010, 0x00FA, 0x5121, 0x9234, 0xE490, 0xEAAE, 0xF22A, 0xF334, 0xFA5A, 0xFB6A, 0xF000,
+            0xF102,
         ] {
             assert_eq!(
                 Instruction::try_from(opcode),
@@ -177,4 +355,14 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn encoding_round_trips_every_decodable_opcode() {
+        for opcode in 0..=u16::MAX {
+            if let Ok(instruction) = Instruction::try_from(opcode) {
+                assert_eq!(u16::from(&instruction), opcode);
+            }
+        }
+        assert_eq!(u16::from(Instruction::InsF000(0x1234)), LONG_OPCODE_PREFIX);
+    }
 }
@@ -1,5 +1,28 @@
 #![no_std]
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+pub mod analysis;
 pub mod chip8;
+pub mod debugger;
+#[cfg(feature = "alloc")]
+pub mod decode_cache;
+pub mod disassembler;
+pub mod display;
 pub mod error;
+pub mod fontset;
+pub mod frame;
+pub mod idle;
 pub mod instruction;
+pub mod machine;
+pub mod movie;
+#[cfg(feature = "alloc")]
+pub mod profile;
+pub mod quirks;
+#[cfg(feature = "alloc")]
+pub mod rewind;
 pub mod rng;
+pub mod snapshot;
+pub mod timing;
+pub mod trace;
@@ -0,0 +1,180 @@
+use crate::{
+    chip8::{
+        BIG_FONTSET_SIZE, FONTSET_SIZE, FONTSET_START, MEMORY_SIZE, PROGRAM_START, XO_MEMORY_SIZE,
+    },
+    error::ConfigError,
+};
+
+// Both fonts, the small one followed by the big one
+pub const FONT_MEMORY_SIZE: usize = FONTSET_SIZE + BIG_FONTSET_SIZE;
+
+// Where things live in memory. These differ between machines, e.g. the
+// ETI-660 loads programs at 0x600 and XO-CHIP addresses 64 KiB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MachineConfig {
+    // Addressable memory, from 0
+    pub memory_size: usize,
+    // Where load_rom puts the program and execution starts
+    pub program_start: u16,
+    // The small font, with the big one right after it
+    pub fontset_start: usize,
+}
+
+impl MachineConfig {
+    pub const CHIP8: MachineConfig = MachineConfig {
+        memory_size: MEMORY_SIZE,
+        program_start: PROGRAM_START,
+        fontset_start: FONTSET_START,
+    };
+
+    pub const CHIP8_2K: MachineConfig = MachineConfig {
+        memory_size: 2048,
+        ..MachineConfig::CHIP8
+    };
+
+    pub const ETI660: MachineConfig = MachineConfig {
+        program_start: 0x600,
+        ..MachineConfig::CHIP8
+    };
+
+    pub const XOCHIP: MachineConfig = MachineConfig {
+        memory_size: XO_MEMORY_SIZE,
+        ..MachineConfig::CHIP8
+    };
+
+    pub fn from_name(name: &str) -> Option<MachineConfig> {
+        match name {
+            "chip8" => Some(MachineConfig::CHIP8),
+            "2k" => Some(MachineConfig::CHIP8_2K),
+            "eti660" => Some(MachineConfig::ETI660),
+            "xochip" => Some(MachineConfig::XOCHIP),
+            _ => None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.memory_size == 0 || self.memory_size > XO_MEMORY_SIZE {
+            return Err(ConfigError::InvalidMemorySize(self.memory_size));
+        }
+        if self.program_start as usize >= self.memory_size {
+            return Err(ConfigError::ProgramStartOutOfBounds {
+                program_start: self.program_start,
+                memory_size: self.memory_size,
+            });
+        }
+        if self.fontset_end() > self.memory_size {
+            return Err(ConfigError::FontsetOutOfBounds {
+                fontset_start: self.fontset_start,
+                memory_size: self.memory_size,
+            });
+        }
+        let program_start = self.program_start as usize;
+        if self.fontset_start <= program_start && self.fontset_end() > program_start {
+            return Err(ConfigError::FontsetOverlapsProgram {
+                fontset_start: self.fontset_start,
+                program_start: self.program_start,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn big_fontset_start(&self) -> usize {
+        self.fontset_start + FONTSET_SIZE
+    }
+
+    pub fn fontset_end(&self) -> usize {
+        self.fontset_start + FONT_MEMORY_SIZE
+    }
+
+    // Programs run up to the end of memory, or up to the fonts if they are
+    // stored above the program
+    pub fn program_end(&self) -> usize {
+        if self.fontset_start >= self.program_start as usize {
+            self.fontset_start
+        } else {
+            self.memory_size
+        }
+    }
+}
+
+impl Default for MachineConfig {
+    fn default() -> Self {
+        MachineConfig::CHIP8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_valid() {
+        for config in [
+            MachineConfig::CHIP8,
+            MachineConfig::CHIP8_2K,
+            MachineConfig::ETI660,
+            MachineConfig::XOCHIP,
+        ] {
+            assert_eq!(config.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn inconsistent_layouts_are_rejected() {
+        let config = MachineConfig {
+            memory_size: XO_MEMORY_SIZE + 1,
+            ..MachineConfig::CHIP8
+        };
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::InvalidMemorySize(XO_MEMORY_SIZE + 1))
+        );
+
+        let config = MachineConfig {
+            program_start: 0x800,
+            ..MachineConfig::CHIP8_2K
+        };
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::ProgramStartOutOfBounds {
+                program_start: 0x800,
+                memory_size: 2048
+            })
+        );
+
+        let config = MachineConfig {
+            fontset_start: MEMORY_SIZE - 16,
+            ..MachineConfig::CHIP8
+        };
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::FontsetOutOfBounds {
+                fontset_start: MEMORY_SIZE - 16,
+                memory_size: MEMORY_SIZE
+            })
+        );
+
+        let config = MachineConfig {
+            fontset_start: 0x1F0,
+            ..MachineConfig::CHIP8
+        };
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::FontsetOverlapsProgram {
+                fontset_start: 0x1F0,
+                program_start: PROGRAM_START
+            })
+        );
+    }
+
+    #[test]
+    fn fonts_above_the_program_end_it() {
+        let config = MachineConfig {
+            fontset_start: MEMORY_SIZE - FONT_MEMORY_SIZE,
+            ..MachineConfig::CHIP8
+        };
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(config.program_end(), MEMORY_SIZE - FONT_MEMORY_SIZE);
+        assert_eq!(MachineConfig::CHIP8.program_end(), MEMORY_SIZE);
+    }
+}
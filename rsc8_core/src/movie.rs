@@ -0,0 +1,175 @@
+use crate::{error::MovieError, fontset::Fontset, machine::MachineConfig, quirks::Quirks};
+
+// Movie layout: header, then one little-endian u16 keypad bitmask per frame
+pub const MOVIE_MAGIC: [u8; 4] = *b"R8MV";
+pub const MOVIE_VERSION: u8 = 1;
+pub const MOVIE_HEADER_SIZE: usize = MOVIE_MAGIC.len() + 1 + 8 + 8 + 1 + 4 + 2 + 2 + 1 + 8;
+pub const MOVIE_FRAME_SIZE: usize = 2;
+
+const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovieHeader {
+    pub rng_state: u64,
+    pub rom_hash: u64,
+    pub quirks: Quirks,
+    pub config: MachineConfig,
+    // Frames run the VIP cycle budget instead of a fixed instruction count
+    pub vip_timing: bool,
+    // ROMs can read the font, replays need the same one
+    pub font_hash: u64,
+}
+
+impl MovieHeader {
+    pub fn write(&self, buffer: &mut [u8]) -> Result<usize, MovieError> {
+        if buffer.len() < MOVIE_HEADER_SIZE {
+            return Err(MovieError::Truncated {
+                required: MOVIE_HEADER_SIZE,
+                available: buffer.len(),
+            });
+        }
+        buffer[..4].copy_from_slice(&MOVIE_MAGIC);
+        buffer[4] = MOVIE_VERSION;
+        buffer[5..13].copy_from_slice(&self.rng_state.to_le_bytes());
+        buffer[13..21].copy_from_slice(&self.rom_hash.to_le_bytes());
+        buffer[21] = self.quirks.bits();
+        buffer[22..26].copy_from_slice(&(self.config.memory_size as u32).to_le_bytes());
+        buffer[26..28].copy_from_slice(&self.config.program_start.to_le_bytes());
+        buffer[28..30].copy_from_slice(&(self.config.fontset_start as u16).to_le_bytes());
+        buffer[30] = self.vip_timing as u8;
+        buffer[31..39].copy_from_slice(&self.font_hash.to_le_bytes());
+        Ok(MOVIE_HEADER_SIZE)
+    }
+
+    pub fn read(buffer: &[u8]) -> Result<MovieHeader, MovieError> {
+        if buffer.len() < MOVIE_HEADER_SIZE {
+            return Err(MovieError::Truncated {
+                required: MOVIE_HEADER_SIZE,
+                available: buffer.len(),
+            });
+        }
+        if buffer[..4] != MOVIE_MAGIC {
+            return Err(MovieError::InvalidMagic);
+        }
+        if buffer[4] != MOVIE_VERSION {
+            return Err(MovieError::UnsupportedVersion(buffer[4]));
+        }
+        let u64_at = |offset: usize| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&buffer[offset..offset + 8]);
+            u64::from_le_bytes(bytes)
+        };
+        let u16_at = |offset: usize| u16::from_le_bytes([buffer[offset], buffer[offset + 1]]);
+        let mut memory_size = [0; 4];
+        memory_size.copy_from_slice(&buffer[22..26]);
+        Ok(MovieHeader {
+            rng_state: u64_at(5),
+            rom_hash: u64_at(13),
+            quirks: Quirks::from_bits(buffer[21]),
+            config: MachineConfig {
+                memory_size: u32::from_le_bytes(memory_size) as usize,
+                program_start: u16_at(26),
+                fontset_start: u16_at(28) as usize,
+            },
+            vip_timing: buffer[30] != 0,
+            font_hash: u64_at(31),
+        })
+    }
+}
+
+// Splits a movie file into its header and per-frame keypad bitmasks
+pub fn parse_movie(buffer: &[u8]) -> Result<(MovieHeader, impl Iterator<Item = u16>), MovieError> {
+    let header = MovieHeader::read(buffer)?;
+    let frames = &buffer[MOVIE_HEADER_SIZE..];
+    if !frames.len().is_multiple_of(MOVIE_FRAME_SIZE) {
+        return Err(MovieError::PartialFrame(frames.len() / MOVIE_FRAME_SIZE));
+    }
+    let frames = frames
+        .chunks_exact(MOVIE_FRAME_SIZE)
+        .map(|frame| u16::from_le_bytes([frame[0], frame[1]]));
+    Ok((header, frames))
+}
+
+// 64-bit FNV-1a, used to make sure a movie is replayed against the same ROM
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    fnv1a(rom)
+}
+
+// The small font followed by the big one
+pub fn font_hash(fontset: &Fontset) -> u64 {
+    fnv1a(fontset.small.iter().chain(&fontset.big))
+}
+
+fn fnv1a<'a>(bytes: impl IntoIterator<Item = &'a u8>) -> u64 {
+    bytes.into_iter().fold(FNV_OFFSET_BASIS, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_round_trip() {
+        let header = MovieHeader {
+            rng_state: 0x1234,
+            rom_hash: rom_hash(&[0x00, 0xE0]),
+            quirks: Quirks::SCHIP11,
+            config: MachineConfig::ETI660,
+            vip_timing: true,
+            font_hash: font_hash(&Fontset::OCTO),
+        };
+        let mut buffer = [0; MOVIE_HEADER_SIZE];
+        assert_eq!(header.write(&mut buffer), Ok(MOVIE_HEADER_SIZE));
+        assert_eq!(MovieHeader::read(&buffer), Ok(header));
+    }
+
+    #[test]
+    fn parse_movie_yields_frames_and_rejects_partial_frame() {
+        let header = MovieHeader {
+            rng_state: 1,
+            rom_hash: 2,
+            quirks: Quirks::VIP,
+            config: MachineConfig::CHIP8,
+            vip_timing: false,
+            font_hash: 3,
+        };
+        let mut buffer = [0; MOVIE_HEADER_SIZE + 5];
+        header.write(&mut buffer).unwrap();
+        buffer[MOVIE_HEADER_SIZE..MOVIE_HEADER_SIZE + 4].copy_from_slice(&[0x01, 0x00, 0x00, 0x80]);
+
+        let (_, frames) = parse_movie(&buffer[..MOVIE_HEADER_SIZE + 4]).unwrap();
+        let mut frames = frames;
+        assert_eq!(frames.next(), Some(0x0001));
+        assert_eq!(frames.next(), Some(0x8000));
+        assert_eq!(frames.next(), None);
+
+        assert!(matches!(
+            parse_movie(&buffer),
+            Err(MovieError::PartialFrame(2))
+        ));
+    }
+
+    #[test]
+    fn read_rejects_bad_magic_and_version() {
+        let mut buffer = [0; MOVIE_HEADER_SIZE];
+        assert_eq!(MovieHeader::read(&buffer), Err(MovieError::InvalidMagic));
+        buffer[..4].copy_from_slice(&MOVIE_MAGIC);
+        buffer[4] = MOVIE_VERSION + 1;
+        assert_eq!(
+            MovieHeader::read(&buffer),
+            Err(MovieError::UnsupportedVersion(MOVIE_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn rom_hash_distinguishes_roms() {
+        assert_eq!(rom_hash(&[]), FNV_OFFSET_BASIS);
+        assert_ne!(rom_hash(&[0x12, 0x00]), rom_hash(&[0x00, 0x12]));
+        let mut fontset = Fontset::OCTO;
+        fontset.big[0] ^= 1;
+        assert_ne!(font_hash(&fontset), font_hash(&Fontset::OCTO));
+    }
+}
@@ -0,0 +1,294 @@
+// Execution counts per address, data reads and writes per byte, and the
+// instructions spent in every subroutine from its CALL to its RET, callees
+// included
+use crate::{
+    chip8::{AUDIO_PATTERN_SIZE, Chip8, PLANE_COUNT},
+    disassembler::{Disassembly, Line, LineKind},
+    instruction::Instruction,
+    rng::RandomSource,
+};
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+use core::fmt::{self, Write};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubroutineStats {
+    pub calls: u64,
+    pub instructions: u64,
+}
+
+pub struct Profile {
+    executions: Vec<u64>,
+    reads: Vec<u64>,
+    writes: Vec<u64>,
+    subroutines: BTreeMap<u16, SubroutineStats>,
+    // Subroutine address and instruction count at every CALL not yet returned
+    calls: Vec<(u16, u64)>,
+    instructions: u64,
+}
+
+impl Profile {
+    pub fn new(memory_size: usize) -> Self {
+        Self {
+            executions: vec![0; memory_size],
+            reads: vec![0; memory_size],
+            writes: vec![0; memory_size],
+            subroutines: BTreeMap::new(),
+            calls: Vec::new(),
+            instructions: 0,
+        }
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn executions(&self, address: u16) -> u64 {
+        self.executions.get(address as usize).copied().unwrap_or(0)
+    }
+
+    pub fn reads(&self, address: u16) -> u64 {
+        self.reads.get(address as usize).copied().unwrap_or(0)
+    }
+
+    pub fn writes(&self, address: u16) -> u64 {
+        self.writes.get(address as usize).copied().unwrap_or(0)
+    }
+
+    pub fn subroutines(&self) -> &BTreeMap<u16, SubroutineStats> {
+        &self.subroutines
+    }
+
+    // Call once before every Chip8::tick, the instruction at the program
+    // counter is the one counted
+    pub fn record<R, const CAPACITY: usize>(&mut self, chip8: &Chip8<R, CAPACITY>)
+    where
+        R: RandomSource,
+    {
+        let instructions = self.instructions;
+        self.instructions += 1;
+        count(&mut self.executions, chip8.program_counter as usize, 1);
+
+        let Ok(instruction) = chip8.peek_instruction() else {
+            return;
+        };
+        let i = chip8.register_i as usize;
+        match instruction {
+            Instruction::Ins2NNN(nnn) => self.calls.push((nnn, instructions)),
+            // Returns without a matching call, e.g. when profiling started
+            // inside a subroutine, are not attributed
+            Instruction::Ins00EE => {
+                if let Some((address, called_at)) = self.calls.pop() {
+                    let stats = self.subroutines.entry(address).or_default();
+                    stats.calls += 1;
+                    stats.instructions += instructions - called_at;
+                }
+            }
+            Instruction::Ins5XY2(x, y) => count(&mut self.writes, i, x.abs_diff(y) as usize + 1),
+            Instruction::Ins5XY3(x, y) => count(&mut self.reads, i, x.abs_diff(y) as usize + 1),
+            Instruction::InsDXYN(_, y, n) => self.count_sprite_reads(chip8, y, n),
+            Instruction::InsF002 => count(&mut self.reads, i, AUDIO_PATTERN_SIZE),
+            Instruction::InsFX33(_) => count(&mut self.writes, i, 3),
+            Instruction::InsFX55(x) => count(&mut self.writes, i, x as usize + 1),
+            Instruction::InsFX65(x) => count(&mut self.reads, i, x as usize + 1),
+            _ => {}
+        }
+    }
+
+    // Mirrors Chip8::draw_sprite, rows below the screen are not read unless
+    // sprites wrap
+    fn count_sprite_reads<R, const CAPACITY: usize>(
+        &mut self,
+        chip8: &Chip8<R, CAPACITY>,
+        y: u8,
+        n: u8,
+    ) where
+        R: RandomSource,
+    {
+        let (bytes_per_row, sprite_height) = if n == 0 { (2, 16) } else { (1, n as usize) };
+        let height = chip8.screen_height();
+        let vy = chip8.register_v[y as usize] as usize % height;
+        let rows = if chip8.quirks.sprite_wrap {
+            sprite_height
+        } else {
+            sprite_height.min(height - vy)
+        };
+        let mut sprite_address = chip8.register_i as usize;
+        for plane in 0..PLANE_COUNT {
+            if chip8.selected_planes & (1 << plane) != 0 {
+                count(&mut self.reads, sprite_address, rows * bytes_per_row);
+                sprite_address += sprite_height * bytes_per_row;
+            }
+        }
+    }
+
+    pub fn write_json<W: Write>(&self, out: &mut W) -> fmt::Result {
+        write!(out, "{{\n  \"instructions\": {},\n", self.instructions)?;
+        for (name, counts) in [
+            ("executions", &self.executions),
+            ("reads", &self.reads),
+            ("writes", &self.writes),
+        ] {
+            write!(out, "  \"{name}\": {{")?;
+            let mut separator = "";
+            for (address, count) in counts.iter().enumerate() {
+                if *count > 0 {
+                    write!(out, "{separator}\n    \"0x{address:03X}\": {count}")?;
+                    separator = ",";
+                }
+            }
+            write!(out, "\n  }},\n")?;
+        }
+        write!(out, "  \"subroutines\": {{")?;
+        let mut separator = "";
+        for (address, stats) in &self.subroutines {
+            write!(
+                out,
+                "{separator}\n    \"0x{address:03X}\": {{\"calls\": {}, \"instructions\": {}}}",
+                stats.calls, stats.instructions
+            )?;
+            separator = ",";
+        }
+        writeln!(out, "\n  }}\n}}")
+    }
+
+    // The listing with how often every instruction ran and how often every
+    // data byte was read and written. Code that never ran is marked, it is
+    // either dead or was not reached in this run.
+    pub fn annotate<'a>(&'a self, disassembly: &'a Disassembly) -> Annotated<'a> {
+        Annotated {
+            profile: self,
+            disassembly,
+        }
+    }
+}
+
+fn count(counts: &mut [u64], start: usize, len: usize) {
+    // Accesses past the end of memory fail, there is nothing to count
+    let end = (start + len).min(counts.len());
+    for count in counts.get_mut(start..end).unwrap_or_default() {
+        *count += 1;
+    }
+}
+
+pub struct Annotated<'a> {
+    profile: &'a Profile,
+    disassembly: &'a Disassembly,
+}
+
+impl fmt::Display for Annotated<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.disassembly
+            .write_annotated(f, |line| Some(LineProfile(self.profile, line)))
+    }
+}
+
+struct LineProfile<'a>(&'a Profile, &'a Line);
+
+impl fmt::Display for LineProfile<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let LineProfile(profile, line) = *self;
+        let addresses = line.address..=line.address + (line.size as u16 - 1);
+        if let LineKind::Code(_) = line.kind {
+            match profile.executions(line.address) {
+                0 => write!(f, "never ran")?,
+                count => write!(f, "ran {count}x")?,
+            }
+            if let Some(stats) = profile.subroutines.get(&line.address) {
+                write!(
+                    f,
+                    ", {} calls, {} instructions",
+                    stats.calls, stats.instructions
+                )?;
+            }
+        } else {
+            let reads: u64 = addresses
+                .clone()
+                .map(|address| profile.reads(address))
+                .sum();
+            let writes: u64 = addresses.map(|address| profile.writes(address)).sum();
+            write!(f, "read {reads}x, written {writes}x")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chip8::{PROGRAM_START, XO_MEMORY_SIZE},
+        disassembler::{Syntax, disassemble},
+        rng::FixedSequence,
+    };
+    use alloc::string::{String, ToString};
+
+    // 2206, 1202, 00E0, A20E, F165, 00EE, then two bytes of data at 0x20E
+    const ROM: [u8; 16] = [
+        0x22, 0x06, 0x12, 0x02, 0x00, 0xE0, 0xA2, 0x0E, 0xF1, 0x65, 0x00, 0xEE, 0x00, 0x00, 0x12,
+        0x34,
+    ];
+
+    fn profile_rom(ticks: usize) -> Profile {
+        let mut chip8 = Chip8::new(FixedSequence::new(&[0]));
+        chip8.load_rom(&ROM).unwrap();
+        let mut profile = Profile::new(chip8.memory_size());
+        for _ in 0..ticks {
+            profile.record(&chip8);
+            chip8.tick().unwrap();
+        }
+        profile
+    }
+
+    #[test]
+    fn counts_executions_accesses_and_subroutines() {
+        let profile = profile_rom(7);
+        assert_eq!(profile.instructions(), 7);
+        assert_eq!(profile.executions(0x200), 1);
+        assert_eq!(profile.executions(0x202), 3);
+        assert_eq!(profile.executions(0x204), 0);
+        assert_eq!(profile.executions(0x206), 1);
+        assert_eq!((profile.reads(0x20E), profile.reads(0x20F)), (1, 1));
+        assert_eq!(profile.reads(0x210), 0);
+        assert_eq!(profile.writes(0x20E), 0);
+        assert_eq!(
+            profile.subroutines().get(&0x206),
+            Some(&SubroutineStats {
+                calls: 1,
+                instructions: 3
+            })
+        );
+    }
+
+    #[test]
+    fn exports_json_and_annotated_listing() {
+        let profile = profile_rom(5);
+        let mut json = String::new();
+        profile.write_json(&mut json).unwrap();
+        assert_eq!(
+            json,
+            "{\n  \"instructions\": 5,\n  \"executions\": {\n    \"0x200\": 1,\n    \"0x202\": 1,\
+             \n    \"0x206\": 1,\n    \"0x208\": 1,\n    \"0x20A\": 1\n  },\n  \"reads\": {\
+             \n    \"0x20E\": 1,\n    \"0x20F\": 1\n  },\n  \"writes\": {\n  },\
+             \n  \"subroutines\": {\n    \"0x206\": {\"calls\": 1, \"instructions\": 3}\n  }\n}\n"
+        );
+
+        let disassembly = disassemble(&ROM, PROGRAM_START, Syntax::Standard);
+        let listing = profile.annotate(&disassembly).to_string();
+        assert!(listing.contains("    0x200  2206      CALL sub_206  ; ran 1x\n"));
+        assert!(listing.contains("    0x204  00E0      CLS  ; never ran\n"));
+        assert!(listing.contains("LD I, data_20E  ; ran 1x, 1 calls, 3 instructions\n"));
+        assert!(listing.contains("    0x20F  34        DB 0x34  ; read 1x, written 0x\n"));
+    }
+
+    #[test]
+    fn annotates_lines_at_the_top_of_memory() {
+        let mut profile = Profile::new(XO_MEMORY_SIZE);
+        profile.reads[0xFFFF] = 2;
+        let disassembly = disassemble(&[0xFF, 0xFF], 0xFFFE, Syntax::Standard);
+        let listing = profile.annotate(&disassembly).to_string();
+        assert_eq!(
+            listing,
+            "    0xFFFE  FFFF      DB 0xFF, 0xFF  ; read 2x, written 0x\n"
+        );
+    }
+}
@@ -0,0 +1,159 @@
+// Behaviours that differ between CHIP-8 interpreters.
+// See https://github.com/Timendus/chip8-test-suite#quirks-test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    // 8XY1, 8XY2 and 8XY3 reset VF to 0
+    pub vf_reset: bool,
+    // 8XY6 and 8XYE copy VY into VX before shifting
+    pub shift_uses_vy: bool,
+    // How far FX55 and FX65 move I
+    pub memory_increment: MemoryIncrement,
+    // BNNN is BXNN and jumps to XNN + VX instead of NNN + V0
+    pub jump_uses_vx: bool,
+    // DXYN wraps sprites around the screen edge instead of clipping them
+    pub sprite_wrap: bool,
+    // DXYN waits for the next timer tick (vertical blank) before continuing
+    pub display_wait: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryIncrement {
+    // I is left alone
+    None,
+    // I advances by X, one short of the last register (CHIP-48)
+    X,
+    // I points past the last register (COSMAC VIP)
+    XPlusOne,
+}
+
+impl MemoryIncrement {
+    pub fn amount(&self, x: u8) -> u16 {
+        match self {
+            MemoryIncrement::None => 0,
+            MemoryIncrement::X => x as u16,
+            MemoryIncrement::XPlusOne => x as u16 + 1,
+        }
+    }
+}
+
+impl Quirks {
+    pub const VIP: Quirks = Quirks {
+        vf_reset: true,
+        shift_uses_vy: true,
+        memory_increment: MemoryIncrement::XPlusOne,
+        jump_uses_vx: false,
+        sprite_wrap: false,
+        display_wait: true,
+    };
+
+    pub const CHIP48: Quirks = Quirks {
+        vf_reset: false,
+        shift_uses_vy: false,
+        memory_increment: MemoryIncrement::X,
+        jump_uses_vx: true,
+        sprite_wrap: false,
+        display_wait: false,
+    };
+
+    pub const SCHIP11: Quirks = Quirks {
+        vf_reset: false,
+        shift_uses_vy: false,
+        memory_increment: MemoryIncrement::None,
+        jump_uses_vx: true,
+        sprite_wrap: false,
+        display_wait: false,
+    };
+
+    pub const XOCHIP: Quirks = Quirks {
+        vf_reset: false,
+        shift_uses_vy: true,
+        memory_increment: MemoryIncrement::XPlusOne,
+        jump_uses_vx: false,
+        sprite_wrap: true,
+        display_wait: false,
+    };
+
+    // Packs the flags into one byte, in field order from the lowest bit.
+    // Bit 2 is set for XPlusOne, bit 6 for X.
+    pub fn bits(&self) -> u8 {
+        [
+            self.vf_reset,
+            self.shift_uses_vy,
+            self.memory_increment == MemoryIncrement::XPlusOne,
+            self.jump_uses_vx,
+            self.sprite_wrap,
+            self.display_wait,
+            self.memory_increment == MemoryIncrement::X,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (index, &flag)| bits | ((flag as u8) << index))
+    }
+
+    pub fn from_bits(bits: u8) -> Quirks {
+        let flag = |index: u8| bits & (1 << index) != 0;
+        Quirks {
+            vf_reset: flag(0),
+            shift_uses_vy: flag(1),
+            memory_increment: if flag(2) {
+                MemoryIncrement::XPlusOne
+            } else if flag(6) {
+                MemoryIncrement::X
+            } else {
+                MemoryIncrement::None
+            },
+            jump_uses_vx: flag(3),
+            sprite_wrap: flag(4),
+            display_wait: flag(5),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Quirks> {
+        match name {
+            "vip" | "chip8" => Some(Quirks::VIP),
+            "chip48" => Some(Quirks::CHIP48),
+            "schip" | "schip11" => Some(Quirks::SCHIP11),
+            "xochip" => Some(Quirks::XOCHIP),
+            _ => None,
+        }
+    }
+}
+
+// The VIP quirks without display_wait, which is how Chip8 behaved before
+// quirks were configurable
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            vf_reset: true,
+            shift_uses_vy: true,
+            memory_increment: MemoryIncrement::XPlusOne,
+            jump_uses_vx: false,
+            sprite_wrap: false,
+            display_wait: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Quirks;
+
+    #[test]
+    fn bits_round_trip_for_all_presets() {
+        for quirks in [Quirks::VIP, Quirks::CHIP48, Quirks::SCHIP11, Quirks::XOCHIP] {
+            assert_eq!(Quirks::from_bits(quirks.bits()), quirks);
+        }
+    }
+
+    #[test]
+    fn default_does_not_wait_for_vblank() {
+        assert!(!Quirks::default().display_wait);
+        assert_eq!(
+            Quirks {
+                display_wait: true,
+                ..Quirks::default()
+            },
+            Quirks::VIP
+        );
+    }
+}
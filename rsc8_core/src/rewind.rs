@@ -0,0 +1,224 @@
+use crate::{chip8::Chip8, error::SnapshotError, rng::RandomSource};
+use alloc::{collections::VecDeque, vec::Vec};
+
+// Keeps the latest snapshot in full and every older frame as an XOR delta
+// against its successor, run-length encoded because consecutive frames
+// differ in only a handful of bytes.
+pub struct Rewind {
+    capacity: usize,
+    current: Vec<u8>,
+    scratch: Vec<u8>,
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl Rewind {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            current: Vec::new(),
+            scratch: Vec::new(),
+            deltas: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    // Number of frames that can be stepped back
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.current.clear();
+        self.deltas.clear();
+    }
+
+    pub fn record<R, const CAPACITY: usize>(
+        &mut self,
+        chip8: &Chip8<R, CAPACITY>,
+    ) -> Result<(), SnapshotError>
+    where
+        R: RandomSource,
+    {
+        self.scratch.resize(chip8.snapshot_size(), 0);
+        chip8.save_snapshot(&mut self.scratch)?;
+
+        // A different snapshot size means a different machine layout, so
+        // older frames cannot be reconstructed from the new one
+        if self.current.len() != self.scratch.len() {
+            self.deltas.clear();
+            core::mem::swap(&mut self.current, &mut self.scratch);
+            return Ok(());
+        }
+
+        let delta = if self.deltas.len() == self.capacity {
+            let mut recycled = self.deltas.pop_front().unwrap_or_default();
+            recycled.clear();
+            recycled
+        } else {
+            Vec::new()
+        };
+        if self.capacity > 0 {
+            self.deltas
+                .push_back(encode_delta(&self.current, &self.scratch, delta));
+        }
+        core::mem::swap(&mut self.current, &mut self.scratch);
+        Ok(())
+    }
+
+    // Restores the state recorded `frames` frames ago, or the oldest one
+    // available. Returns how many frames were actually stepped back.
+    pub fn step_back<R, const CAPACITY: usize>(
+        &mut self,
+        chip8: &mut Chip8<R, CAPACITY>,
+        frames: usize,
+    ) -> Result<usize, SnapshotError>
+    where
+        R: RandomSource,
+    {
+        let mut stepped = 0;
+        while stepped < frames {
+            let Some(delta) = self.deltas.pop_back() else {
+                break;
+            };
+            apply_delta(&delta, &mut self.current);
+            stepped += 1;
+        }
+        if stepped > 0 {
+            chip8.restore_snapshot(&self.current)?;
+        }
+        Ok(stepped)
+    }
+}
+
+// Delta format: repeated (zero run length, literal length, literal bytes),
+// lengths as LEB128 varints
+fn encode_delta(previous: &[u8], next: &[u8], mut delta: Vec<u8>) -> Vec<u8> {
+    let mut xor = previous.iter().zip(next).map(|(a, b)| a ^ b).peekable();
+    while xor.peek().is_some() {
+        let mut zero_run = 0;
+        while xor.next_if_eq(&0).is_some() {
+            zero_run += 1;
+        }
+        let mut literal = Vec::new();
+        while let Some(byte) = xor.next_if(|byte| *byte != 0) {
+            literal.push(byte);
+        }
+        push_varint(&mut delta, zero_run);
+        push_varint(&mut delta, literal.len());
+        delta.extend_from_slice(&literal);
+    }
+    delta
+}
+
+fn apply_delta(delta: &[u8], state: &mut [u8]) {
+    let mut input = delta.iter().copied();
+    let mut position = 0;
+    while let Some(zero_run) = take_varint(&mut input) {
+        position += zero_run;
+        let literal_len = take_varint(&mut input).unwrap_or_default();
+        for (byte, delta) in state[position..position + literal_len]
+            .iter_mut()
+            .zip(&mut input)
+        {
+            *byte ^= delta;
+        }
+        position += literal_len;
+    }
+}
+
+fn push_varint(output: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        output.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+fn take_varint(input: &mut impl Iterator<Item = u8>) -> Option<usize> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = input.next()?;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::LinearCongruentialGenerator;
+
+    fn new_chip8() -> Chip8<LinearCongruentialGenerator> {
+        let mut chip8 = Chip8::new(LinearCongruentialGenerator::default());
+        // 7001, C1FF, 1200
+        chip8
+            .load_rom(&[0x70, 0x01, 0xC1, 0xFF, 0x12, 0x00])
+            .unwrap();
+        chip8
+    }
+
+    fn run_frame(chip8: &mut Chip8<LinearCongruentialGenerator>) {
+        for _ in 0..3 {
+            chip8.tick().unwrap();
+        }
+        chip8.tick_timer();
+    }
+
+    #[test]
+    fn delta_round_trip_reconstructs_previous_state() {
+        let previous = [0, 0, 0, 1, 2, 3, 0, 0, 9];
+        let next = [0, 0, 0, 1, 7, 3, 5, 0, 9];
+        let delta = encode_delta(&previous, &next, Vec::new());
+        let mut state = next;
+        apply_delta(&delta, &mut state);
+        assert_eq!(state, previous);
+    }
+
+    #[test]
+    fn step_back_restores_earlier_frames() {
+        let mut chip8 = new_chip8();
+        let mut rewind = Rewind::new(8);
+        rewind.record(&chip8).unwrap();
+        for _ in 0..5 {
+            run_frame(&mut chip8);
+            rewind.record(&chip8).unwrap();
+        }
+        assert_eq!(chip8.register_v[0], 5);
+        assert_eq!(rewind.len(), 5);
+
+        assert_eq!(rewind.step_back(&mut chip8, 2).unwrap(), 2);
+        assert_eq!(chip8.register_v[0], 3);
+        let random = chip8.register_v[1];
+
+        // Replaying from a rewound state is deterministic
+        run_frame(&mut chip8);
+        rewind.record(&chip8).unwrap();
+        assert_eq!(rewind.step_back(&mut chip8, 1).unwrap(), 1);
+        assert_eq!(chip8.register_v[1], random);
+        run_frame(&mut chip8);
+        assert_eq!(chip8.register_v[0], 4);
+    }
+
+    #[test]
+    fn history_is_bounded_by_capacity() {
+        let mut chip8 = new_chip8();
+        let mut rewind = Rewind::new(3);
+        rewind.record(&chip8).unwrap();
+        for _ in 0..10 {
+            run_frame(&mut chip8);
+            rewind.record(&chip8).unwrap();
+        }
+        assert_eq!(rewind.len(), 3);
+        assert_eq!(rewind.step_back(&mut chip8, 100).unwrap(), 3);
+        assert_eq!(chip8.register_v[0], 7);
+        assert!(rewind.is_empty());
+        assert_eq!(rewind.step_back(&mut chip8, 1).unwrap(), 0);
+    }
+}
@@ -4,6 +4,22 @@ const DEFAULT_SEED: u16 = 996;
 const LCG_A: u16 = 25_173;
 const LCG_C: u16 = 13_849;
 
+const XORSHIFT_DEFAULT_STATE: u64 = 0x9E37_79B9_7F4A_7C15;
+const XORSHIFT_MULTIPLIER: u64 = 0x2545_F491_4F6C_DD1D;
+
+const PCG_MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+const PCG_INCREMENT: u64 = 1_442_695_040_888_963_407;
+
+// Where CXNN gets its random bytes from. The whole generator state fits in
+// a u64 so it can be saved and restored, e.g. in snapshots and movies.
+pub trait RandomSource {
+    fn next_byte(&mut self) -> u8;
+    // Restarts the sequence from a seed, scrambling it where the generator needs to
+    fn seed(&mut self, seed: u64);
+    fn state(&self) -> u64;
+    fn set_state(&mut self, state: u64);
+}
+
 pub struct LinearCongruentialGenerator {
     pub seed: u16,
 }
@@ -14,12 +30,210 @@ impl Default for LinearCongruentialGenerator {
     }
 }
 
+impl LinearCongruentialGenerator {
+    pub fn next_u16(&mut self) -> u16 {
+        self.seed = LCG_A.wrapping_mul(self.seed).wrapping_add(LCG_C);
+        self.seed
+    }
+}
+
 impl Iterator for LinearCongruentialGenerator {
     type Item = u16;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.seed = LCG_A.wrapping_mul(self.seed).wrapping_add(LCG_C);
-        Some(self.seed)
+        Some(self.next_u16())
+    }
+}
+
+impl RandomSource for LinearCongruentialGenerator {
+    // The low bits of a power-of-two LCG have short periods, the low bit
+    // alternates, so only the high byte is used
+    fn next_byte(&mut self) -> u8 {
+        (self.next_u16() >> 8) as u8
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.seed = seed as u16;
+    }
+
+    fn state(&self) -> u64 {
+        self.seed as u64
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.seed = state as u16;
+    }
+}
+
+// Marsaglia's xorshift64*, a zero state would stay zero forever
+pub struct Xorshift {
+    state: u64,
+}
+
+impl Default for Xorshift {
+    fn default() -> Self {
+        Self {
+            state: XORSHIFT_DEFAULT_STATE,
+        }
+    }
+}
+
+impl Xorshift {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Self::default();
+        rng.seed(seed);
+        rng
+    }
+}
+
+impl RandomSource for Xorshift {
+    fn next_byte(&mut self) -> u8 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        (x.wrapping_mul(XORSHIFT_MULTIPLIER) >> 56) as u8
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.set_state(seed);
+    }
+
+    fn state(&self) -> u64 {
+        self.state
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.state = if state == 0 {
+            XORSHIFT_DEFAULT_STATE
+        } else {
+            state
+        };
+    }
+}
+
+// PCG-XSH-RR 64/32 with the reference increment
+#[derive(Default)]
+pub struct Pcg {
+    state: u64,
+}
+
+impl Pcg {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Self::default();
+        rng.seed(seed);
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let state = self.state;
+        self.step();
+        let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+        xorshifted.rotate_right((state >> 59) as u32)
+    }
+
+    fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(PCG_MULTIPLIER)
+            .wrapping_add(PCG_INCREMENT);
+    }
+}
+
+impl RandomSource for Pcg {
+    fn next_byte(&mut self) -> u8 {
+        (self.next_u32() >> 24) as u8
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.state = 0;
+        self.step();
+        self.state = self.state.wrapping_add(seed);
+        self.step();
+    }
+
+    fn state(&self) -> u64 {
+        self.state
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.state = state;
+    }
+}
+
+// The COSMAC VIP interpreter's CXNN routine. R9 is the generator: each call
+// increments its low byte, uses it to index the interpreter's own code in
+// page 1 of memory (0x100-0x1FF), and adds that byte to its high byte, which
+// is the result before masking. The interpreter is not part of this crate,
+// so the page is passed in from a VIP memory dump.
+pub struct VipRandom<'a> {
+    page: &'a [u8; 256],
+    r9: u16,
+}
+
+impl<'a> VipRandom<'a> {
+    pub fn new(page: &'a [u8; 256], seed: u64) -> Self {
+        let mut rng = Self { page, r9: 0 };
+        rng.seed(seed);
+        rng
+    }
+}
+
+impl RandomSource for VipRandom<'_> {
+    fn next_byte(&mut self) -> u8 {
+        let [high, low] = self.r9.to_be_bytes();
+        let low = low.wrapping_add(1);
+        let high = high.wrapping_add(self.page[low as usize]);
+        self.r9 = u16::from_be_bytes([high, low]);
+        high
+    }
+
+    // On the VIP, R9 is whatever the monitor left behind at power on
+    fn seed(&mut self, seed: u64) {
+        self.r9 = seed as u16;
+    }
+
+    fn state(&self) -> u64 {
+        self.r9 as u64
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.r9 = state as u16;
+    }
+}
+
+// Plays back the given bytes in a loop, for tests. Seeding picks the start.
+pub struct FixedSequence<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> FixedSequence<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+}
+
+impl RandomSource for FixedSequence<'_> {
+    fn next_byte(&mut self) -> u8 {
+        let Some(&byte) = self.bytes.get(self.position) else {
+            return 0;
+        };
+        self.position = (self.position + 1) % self.bytes.len();
+        byte
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.set_state(seed);
+    }
+
+    fn state(&self) -> u64 {
+        self.position as u64
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.position = (state as usize).checked_rem(self.bytes.len()).unwrap_or(0);
     }
 }
 
@@ -76,4 +290,75 @@ mod tests {
             assert_ne!(rng.next().unwrap(), seed);
         }
     }
+
+    #[test]
+    fn random_bytes_take_the_high_byte() {
+        let mut rng = LinearCongruentialGenerator::default();
+        let expected = LinearCongruentialGenerator::default().next().unwrap();
+        assert_eq!(rng.next_byte(), (expected >> 8) as u8);
+    }
+
+    #[test]
+    fn xorshift_replaces_a_zero_state() {
+        let mut rng = Xorshift::new(0);
+        assert_ne!(rng.state(), 0);
+        rng.next_byte();
+        assert_ne!(rng.state(), 0);
+    }
+
+    fn assert_restored_state_replays_sequence(rng: &mut impl RandomSource) {
+        rng.next_byte();
+        let state = rng.state();
+        let expected: [u8; 8] = core::array::from_fn(|_| rng.next_byte());
+        rng.seed(12_345);
+        rng.next_byte();
+        rng.set_state(state);
+        let actual: [u8; 8] = core::array::from_fn(|_| rng.next_byte());
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn restored_state_replays_sequence() {
+        let page = core::array::from_fn(|index| index as u8 ^ 0x5A);
+        assert_restored_state_replays_sequence(&mut LinearCongruentialGenerator::default());
+        assert_restored_state_replays_sequence(&mut Xorshift::new(7));
+        assert_restored_state_replays_sequence(&mut Pcg::new(7));
+        assert_restored_state_replays_sequence(&mut VipRandom::new(&page, 7));
+        assert_restored_state_replays_sequence(&mut FixedSequence::new(&[1, 2, 3]));
+    }
+
+    fn assert_covers_every_byte(rng: &mut impl RandomSource) {
+        let mut seen = [false; 256];
+        for _ in 0..4096 {
+            seen[rng.next_byte() as usize] = true;
+        }
+        assert!(seen.iter().all(|&seen| seen));
+    }
+
+    #[test]
+    fn generators_cover_every_byte() {
+        assert_covers_every_byte(&mut LinearCongruentialGenerator::default());
+        assert_covers_every_byte(&mut Xorshift::new(1));
+        assert_covers_every_byte(&mut Pcg::new(1));
+    }
+
+    #[test]
+    fn vip_random_accumulates_interpreter_bytes() {
+        let mut page = [0; 256];
+        page[1] = 0x10;
+        page[2] = 0x22;
+        let mut rng = VipRandom::new(&page, 0x0500);
+        assert_eq!(rng.next_byte(), 0x15);
+        assert_eq!(rng.next_byte(), 0x37);
+        assert_eq!(rng.state(), 0x3702);
+    }
+
+    #[test]
+    fn fixed_sequence_loops_from_seeded_position() {
+        let mut rng = FixedSequence::new(&[1, 2, 3]);
+        rng.seed(4);
+        let bytes: [u8; 4] = core::array::from_fn(|_| rng.next_byte());
+        assert_eq!(bytes, [2, 3, 1, 2]);
+        assert_eq!(FixedSequence::new(&[]).next_byte(), 0);
+    }
 }
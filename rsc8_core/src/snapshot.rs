@@ -0,0 +1,410 @@
+use crate::{
+    chip8::{
+        AUDIO_PATTERN_SIZE, Chip8, KEYPAD_SIZE, KeyWait, NUM_REGISTERS, PLANE_COUNT,
+        RPL_FLAGS_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH, STACK_SIZE, XO_MEMORY_SIZE,
+    },
+    error::SnapshotError,
+    machine::MachineConfig,
+    quirks::Quirks,
+    rng::RandomSource,
+};
+
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"RSC8";
+pub const SNAPSHOT_VERSION: u8 = 1;
+// Magic, version and the machine config: memory size, program start and fontset start
+pub const SNAPSHOT_HEADER_SIZE: usize = SNAPSHOT_MAGIC.len() + 1 + 4 + 2 + 2;
+// Every display row of every plane, big-endian so the leftmost pixel comes first
+const ROW_SIZE: usize = SCREEN_WIDTH / 8;
+const PACKED_SCREEN_SIZE: usize = ROW_SIZE * SCREEN_HEIGHT * PLANE_COUNT;
+const STATE_SIZE: usize = 2 // program_counter
+    + NUM_REGISTERS
+    + 2 // register_i
+    + 2 // delay_timer, sound_timer
+    + STACK_SIZE * 2
+    + 1 // stack_pointer
+    + 2 // keypad
+    + 2 // key_wait register and key
+    + 5 // wait_for_vblank, draw_flag, hires, selected_planes, exited
+    + RPL_FLAGS_SIZE
+    + AUDIO_PATTERN_SIZE
+    + 1 // pitch
+    + 1 // quirks
+    + 8 // rng
+    + PACKED_SCREEN_SIZE;
+pub const MAX_SNAPSHOT_SIZE: usize = snapshot_size(XO_MEMORY_SIZE);
+// Marks no FX0A wait, or no key pressed yet
+const NO_KEY: u8 = 0xFF;
+
+pub const fn snapshot_size(memory_size: usize) -> usize {
+    SNAPSHOT_HEADER_SIZE + STATE_SIZE + memory_size
+}
+
+struct Writer<'a> {
+    buffer: &'a mut [u8],
+    position: usize,
+}
+
+impl Writer<'_> {
+    fn put(&mut self, bytes: &[u8]) {
+        self.buffer[self.position..self.position + bytes.len()].copy_from_slice(bytes);
+        self.position += bytes.len();
+    }
+
+    fn put_u8(&mut self, value: u8) {
+        self.put(&[value]);
+    }
+
+    fn put_u16(&mut self, value: u16) {
+        self.put(&value.to_le_bytes());
+    }
+}
+
+struct Reader<'a> {
+    buffer: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> &'a [u8] {
+        let bytes = &self.buffer[self.position..self.position + len];
+        self.position += len;
+        bytes
+    }
+
+    fn take_array<const N: usize>(&mut self) -> [u8; N] {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N));
+        array
+    }
+
+    fn take_u8(&mut self) -> u8 {
+        self.take(1)[0]
+    }
+
+    fn take_bool(&mut self) -> bool {
+        self.take_u8() != 0
+    }
+
+    fn take_u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.take_array())
+    }
+}
+
+impl<R, const CAPACITY: usize> Chip8<R, CAPACITY>
+where
+    R: RandomSource,
+{
+    pub fn snapshot_size(&self) -> usize {
+        snapshot_size(self.config.memory_size)
+    }
+
+    // Serializes the complete machine state, returns the number of bytes written
+    pub fn save_snapshot(&self, buffer: &mut [u8]) -> Result<usize, SnapshotError> {
+        let required = self.snapshot_size();
+        if buffer.len() < required {
+            return Err(SnapshotError::BufferTooSmall {
+                required,
+                available: buffer.len(),
+            });
+        }
+
+        let mut writer = Writer {
+            buffer,
+            position: 0,
+        };
+        writer.put(&SNAPSHOT_MAGIC);
+        writer.put_u8(SNAPSHOT_VERSION);
+        writer.put(&(self.config.memory_size as u32).to_le_bytes());
+        writer.put_u16(self.config.program_start);
+        writer.put_u16(self.config.fontset_start as u16);
+
+        writer.put_u16(self.program_counter);
+        writer.put(&self.register_v);
+        writer.put_u16(self.register_i);
+        writer.put_u8(self.delay_timer);
+        writer.put_u8(self.sound_timer);
+        self.stack.iter().for_each(|&value| writer.put_u16(value));
+        writer.put_u8(self.stack_pointer);
+        writer.put_u16(self.keypad_bitmask());
+        writer.put_u8(self.key_wait.map_or(NO_KEY, |wait| wait.register));
+        writer.put_u8(
+            self.key_wait
+                .and_then(|wait| wait.key)
+                .map_or(NO_KEY, |key| key as u8),
+        );
+        writer.put_u8(self.wait_for_vblank as u8);
+        writer.put_u8(self.draw_flag as u8);
+        writer.put_u8(self.hires as u8);
+        writer.put_u8(self.selected_planes);
+        writer.put_u8(self.exited as u8);
+        writer.put(&self.rpl_flags);
+        writer.put(&self.audio_pattern);
+        writer.put_u8(self.pitch);
+        writer.put_u8(self.quirks.bits());
+        writer.put(&self.rng.state().to_le_bytes());
+        for plane in 0..PLANE_COUNT {
+            for y in 0..SCREEN_HEIGHT {
+                writer.put(&self.screen.row(plane, y).to_be_bytes());
+            }
+        }
+        writer.put(self.memory());
+
+        Ok(writer.position)
+    }
+
+    // Validates the whole snapshot before touching the machine, so a rejected
+    // snapshot leaves the current state intact
+    pub fn restore_snapshot(&mut self, buffer: &[u8]) -> Result<(), SnapshotError> {
+        if buffer.len() < SNAPSHOT_HEADER_SIZE {
+            return Err(SnapshotError::BufferTooSmall {
+                required: SNAPSHOT_HEADER_SIZE,
+                available: buffer.len(),
+            });
+        }
+        let mut reader = Reader {
+            buffer,
+            position: 0,
+        };
+        if reader.take(SNAPSHOT_MAGIC.len()) != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::InvalidMagic);
+        }
+        let version = reader.take_u8();
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let config = MachineConfig {
+            memory_size: u32::from_le_bytes(reader.take_array()) as usize,
+            program_start: reader.take_u16(),
+            fontset_start: reader.take_u16() as usize,
+        };
+        Self::check_config(&config).map_err(SnapshotError::InvalidConfig)?;
+        let memory_size = config.memory_size;
+        let expected = snapshot_size(memory_size);
+        if buffer.len() != expected {
+            return Err(SnapshotError::InvalidLength {
+                expected,
+                actual: buffer.len(),
+            });
+        }
+
+        let program_counter = reader.take_u16();
+        let register_v = reader.take_array::<NUM_REGISTERS>();
+        let register_i = reader.take_u16();
+        let delay_timer = reader.take_u8();
+        let sound_timer = reader.take_u8();
+        let stack: [u16; STACK_SIZE] = core::array::from_fn(|_| reader.take_u16());
+        let stack_pointer = reader.take_u8();
+        if stack_pointer as usize > STACK_SIZE {
+            return Err(SnapshotError::InvalidStackPointer(stack_pointer));
+        }
+        let keypad = reader.take_u16();
+        let wait_register = reader.take_u8();
+        let wait_key = match reader.take_u8() {
+            NO_KEY => None,
+            key if (key as usize) < KEYPAD_SIZE => Some(key as usize),
+            key => return Err(SnapshotError::InvalidKeyIndex(key)),
+        };
+        let key_wait = match wait_register {
+            NO_KEY => None,
+            register if (register as usize) < NUM_REGISTERS => Some(KeyWait {
+                register,
+                key: wait_key,
+            }),
+            register => return Err(SnapshotError::InvalidRegisterIndex(register)),
+        };
+        let wait_for_vblank = reader.take_bool();
+        let draw_flag = reader.take_bool();
+        let hires = reader.take_bool();
+        let selected_planes = reader.take_u8();
+        let exited = reader.take_bool();
+        let rpl_flags = reader.take_array::<RPL_FLAGS_SIZE>();
+        let audio_pattern = reader.take_array::<AUDIO_PATTERN_SIZE>();
+        let pitch = reader.take_u8();
+        let quirks = Quirks::from_bits(reader.take_u8());
+        let rng_state = u64::from_le_bytes(reader.take_array());
+        let screen = reader.take(PACKED_SCREEN_SIZE);
+        let memory = reader.take(memory_size);
+
+        self.config = config;
+        self.memory[..memory_size].copy_from_slice(memory);
+        self.memory[memory_size..].fill(0);
+        self.invalidate_decode_cache();
+        self.program_counter = program_counter;
+        self.register_v = register_v;
+        self.register_i = register_i;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.stack = stack;
+        self.stack_pointer = stack_pointer;
+        for (key, pressed) in self.keypad.iter_mut().enumerate() {
+            *pressed = keypad & (1 << key) != 0;
+        }
+        self.key_wait = key_wait;
+        self.wait_for_vblank = wait_for_vblank;
+        self.draw_flag = draw_flag;
+        self.hires = hires;
+        self.selected_planes = selected_planes;
+        self.exited = exited;
+        self.rpl_flags = rpl_flags;
+        self.audio_pattern = audio_pattern;
+        self.pitch = pitch;
+        self.quirks = quirks;
+        self.rng.set_state(rng_state);
+        let rows = screen
+            .chunks_exact(ROW_SIZE)
+            .map(|row| u128::from_be_bytes(row.try_into().unwrap()));
+        for (index, row) in rows.enumerate() {
+            self.screen
+                .set_row(index / SCREEN_HEIGHT, index % SCREEN_HEIGHT, row);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chip8::MEMORY_SIZE, error::ConfigError, rng::LinearCongruentialGenerator};
+
+    fn new_chip8() -> Chip8<LinearCongruentialGenerator> {
+        Chip8::new(LinearCongruentialGenerator::default())
+    }
+
+    fn busy_chip8() -> Chip8<LinearCongruentialGenerator> {
+        let mut chip8 = new_chip8();
+        chip8.load_fontset();
+        // 00FF, A000, 6103, D110, C0FF, 2208
+        chip8
+            .load_rom(&[
+                0x00, 0xFF, 0xA0, 0x00, 0x61, 0x03, 0xD1, 0x10, 0xC0, 0xFF, 0x22, 0x08,
+            ])
+            .unwrap();
+        for _ in 0..6 {
+            chip8.tick().unwrap();
+        }
+        chip8.key_wait = Some(KeyWait {
+            register: 2,
+            key: None,
+        });
+        chip8.key_down(0xA);
+        chip8.delay_timer = 30;
+        chip8.sound_timer = 4;
+        chip8.rpl_flags[3] = 7;
+        chip8.audio_pattern[15] = 0xAA;
+        chip8.pitch = 99;
+        chip8
+    }
+
+    #[test]
+    fn snapshot_round_trip_restores_complete_state() {
+        let mut chip8 = busy_chip8();
+        let mut buffer = [0; MAX_SNAPSHOT_SIZE];
+        let size = chip8.save_snapshot(&mut buffer).unwrap();
+        assert_eq!(size, snapshot_size(MEMORY_SIZE));
+
+        let mut restored = new_chip8();
+        restored.quirks = Quirks::XOCHIP;
+        restored.restore_snapshot(&buffer[..size]).unwrap();
+
+        assert_eq!(restored.memory, chip8.memory);
+        assert_eq!(restored.program_counter, chip8.program_counter);
+        assert_eq!(restored.register_v, chip8.register_v);
+        assert_eq!(restored.register_i, chip8.register_i);
+        assert_eq!(restored.delay_timer, chip8.delay_timer);
+        assert_eq!(restored.sound_timer, chip8.sound_timer);
+        assert_eq!(restored.stack, chip8.stack);
+        assert_eq!(restored.stack_pointer, chip8.stack_pointer);
+        assert_eq!(restored.keypad, chip8.keypad);
+        assert_eq!(restored.screen, chip8.screen);
+        assert_eq!(restored.hires, chip8.hires);
+        assert_eq!(restored.rpl_flags, chip8.rpl_flags);
+        assert_eq!(restored.audio_pattern, chip8.audio_pattern);
+        assert_eq!(restored.pitch, chip8.pitch);
+        assert_eq!(restored.quirks, chip8.quirks);
+        assert_eq!(restored.key_wait, chip8.key_wait);
+        assert_eq!(restored.keypad, chip8.keypad);
+        assert_eq!(restored.wait_for_vblank, chip8.wait_for_vblank);
+        assert_eq!(restored.rng.next_u16(), chip8.rng.next_u16());
+    }
+
+    #[test]
+    fn save_snapshot_rejects_small_buffer() {
+        let chip8 = new_chip8();
+        let mut buffer = [0; 16];
+        assert_eq!(
+            chip8.save_snapshot(&mut buffer),
+            Err(SnapshotError::BufferTooSmall {
+                required: snapshot_size(MEMORY_SIZE),
+                available: 16,
+            })
+        );
+    }
+
+    #[test]
+    fn restore_snapshot_validates_header_and_leaves_state_untouched() {
+        let chip8 = busy_chip8();
+        let mut buffer = [0; MAX_SNAPSHOT_SIZE];
+        let size = chip8.save_snapshot(&mut buffer).unwrap();
+
+        let mut target = new_chip8();
+        let mut corrupt = buffer;
+        corrupt[0] = b'X';
+        assert_eq!(
+            target.restore_snapshot(&corrupt[..size]),
+            Err(SnapshotError::InvalidMagic)
+        );
+
+        let mut corrupt = buffer;
+        corrupt[4] = SNAPSHOT_VERSION + 1;
+        assert_eq!(
+            target.restore_snapshot(&corrupt[..size]),
+            Err(SnapshotError::UnsupportedVersion(SNAPSHOT_VERSION + 1))
+        );
+
+        assert_eq!(
+            target.restore_snapshot(&buffer[..size - 1]),
+            Err(SnapshotError::InvalidLength {
+                expected: size,
+                actual: size - 1,
+            })
+        );
+
+        assert_eq!(target.program_counter, new_chip8().program_counter);
+        assert!(target.memory.iter().all(|byte| *byte == 0));
+
+        let mut xo_chip8 = new_chip8();
+        xo_chip8.set_config(MachineConfig::XOCHIP).unwrap();
+        let mut buffer = [0; MAX_SNAPSHOT_SIZE];
+        let size = xo_chip8.save_snapshot(&mut buffer).unwrap();
+        let mut small = Chip8::<_, MEMORY_SIZE>::with_capacity(
+            LinearCongruentialGenerator::default(),
+            Quirks::default(),
+            MachineConfig::CHIP8,
+        )
+        .unwrap();
+        assert_eq!(
+            small.restore_snapshot(&buffer[..size]),
+            Err(SnapshotError::InvalidConfig(
+                ConfigError::MemoryExceedsCapacity {
+                    memory_size: XO_MEMORY_SIZE,
+                    capacity: MEMORY_SIZE
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn restore_snapshot_rejects_corrupt_fields() {
+        let chip8 = new_chip8();
+        let mut buffer = [0; MAX_SNAPSHOT_SIZE];
+        let size = chip8.save_snapshot(&mut buffer).unwrap();
+        let stack_pointer_offset = SNAPSHOT_HEADER_SIZE + 2 + NUM_REGISTERS + 4 + STACK_SIZE * 2;
+        buffer[stack_pointer_offset] = STACK_SIZE as u8 + 1;
+
+        let mut target = new_chip8();
+        assert_eq!(
+            target.restore_snapshot(&buffer[..size]),
+            Err(SnapshotError::InvalidStackPointer(STACK_SIZE as u8 + 1))
+        );
+    }
+}
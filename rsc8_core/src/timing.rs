@@ -0,0 +1,175 @@
+// COSMAC VIP timing model, in CDP1802 machine cycles of 8 clock cycles each.
+// Costs are estimates shaped like the original interpreter, a fixed fetch and
+// decode overhead plus the routine for the instruction, and are not cycle
+// exact. Instructions the VIP interpreter does not have (SUPER-CHIP, XO-CHIP)
+// are charged a flat cost.
+use crate::{chip8::Chip8, error::ExecutionError, instruction::Instruction, rng::RandomSource};
+
+pub const VIP_CLOCK_HZ: u32 = 1_760_640;
+pub const MACHINE_CYCLES_PER_FRAME: u32 = VIP_CLOCK_HZ / 8 / 60;
+// The CDP1861 steals one cycle per displayed byte, 8 bytes on each of 128 lines
+pub const DISPLAY_DMA_CYCLES: u32 = 8 * 128;
+// Vertical blank interrupt routine: display pointer reset and timer countdown
+pub const INTERRUPT_CYCLES: u32 = 46;
+pub const FRAME_BUDGET: u32 = MACHINE_CYCLES_PER_FRAME - DISPLAY_DMA_CYCLES - INTERRUPT_CYCLES;
+
+const FETCH_CYCLES: u32 = 40;
+const SKIP_CYCLES: u32 = 4;
+const DISPLAY_BYTES: u32 = 256;
+// Instructions the VIP interpreter does not implement
+const FOREIGN_CYCLES: u32 = 12;
+
+// Cost of the instruction at the program counter, including the extra work
+// that depends on machine state: taken skips, sprite size and alignment, and
+// the number of subtraction rounds in FX33
+pub fn vip_cycles<R, const CAPACITY: usize>(
+    chip8: &Chip8<R, CAPACITY>,
+    instruction: &Instruction,
+) -> u32
+where
+    R: RandomSource,
+{
+    let v = |x: u8| chip8.register_v[x as usize];
+    let skip = |taken: bool| if taken { SKIP_CYCLES } else { 0 };
+    let key = |x: u8| chip8.keypad.get(v(x) as usize).copied().unwrap_or_default();
+    let execute = match *instruction {
+        Instruction::Ins00E0 => 24 + DISPLAY_BYTES * 4,
+        Instruction::Ins00EE => 10,
+        Instruction::Ins1NNN(_) => 12,
+        Instruction::Ins2NNN(_) => 26,
+        Instruction::Ins3XNN(x, nn) => 10 + skip(v(x) == nn),
+        Instruction::Ins4XNN(x, nn) => 10 + skip(v(x) != nn),
+        Instruction::Ins5XY0(x, y) => 14 + skip(v(x) == v(y)),
+        Instruction::Ins9XY0(x, y) => 14 + skip(v(x) != v(y)),
+        Instruction::Ins6XNN(..) => 6,
+        Instruction::Ins7XNN(..) => 10,
+        // 8XYN assembles and runs a small 1802 routine
+        Instruction::Ins8XY0(..) => 12,
+        Instruction::Ins8XY1(..)
+        | Instruction::Ins8XY2(..)
+        | Instruction::Ins8XY3(..)
+        | Instruction::Ins8XY4(..)
+        | Instruction::Ins8XY5(..)
+        | Instruction::Ins8XY6(..)
+        | Instruction::Ins8XY7(..)
+        | Instruction::Ins8XYE(..) => 44,
+        Instruction::InsANNN(_) => 12,
+        Instruction::InsBNNN(_) => 22,
+        Instruction::InsCXNN(..) => 36,
+        // Every row is shifted into place one bit at a time, so sprites that are
+        // not byte aligned cost more
+        Instruction::InsDXYN(x, _, n) => {
+            let rows = if n == 0 { 16 } else { n as u32 };
+            26 + rows * (16 + 4 * (v(x) % 8) as u32)
+        }
+        Instruction::InsEX9E(x) => 18 + skip(key(x)),
+        Instruction::InsEXA1(x) => 18 + skip(!key(x)),
+        Instruction::InsFX07(_) | Instruction::InsFX15(_) | Instruction::InsFX18(_) => 10,
+        Instruction::InsFX0A(_) => 18,
+        Instruction::InsFX1E(_) => 16,
+        Instruction::InsFX29(_) => 20,
+        // Each decimal digit is found by repeated subtraction
+        Instruction::InsFX33(x) => {
+            let value = v(x) as u32;
+            80 + 16 * (value / 100 + value / 10 % 10 + value % 10)
+        }
+        Instruction::InsFX55(x) | Instruction::InsFX65(x) => 14 + 14 * (x as u32 + 1),
+        _ => FOREIGN_CYCLES,
+    };
+    FETCH_CYCLES + execute
+}
+
+// Runs frames by machine cycle budget instead of instruction count. An
+// instruction that overruns the budget borrows from the next frame; a machine
+// that blocks on the display or a key idles until the next vertical blank.
+// Used through Chip8::run_frame with FrameConfig::VipTiming.
+#[derive(Debug, Clone, Default)]
+pub struct VipTiming {
+    balance: i64,
+}
+
+impl VipTiming {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn start_frame(&mut self) {
+        self.balance = self.balance.min(0) + FRAME_BUDGET as i64;
+    }
+
+    pub fn has_cycles(&self) -> bool {
+        self.balance > 0
+    }
+
+    // Charges the instruction at the program counter, call before Chip8::tick
+    pub fn spend<R, const CAPACITY: usize>(
+        &mut self,
+        chip8: &Chip8<R, CAPACITY>,
+    ) -> Result<u32, ExecutionError>
+    where
+        R: RandomSource,
+    {
+        let instruction = chip8
+            .peek_instruction()
+            .map_err(|error| chip8.execution_error(chip8.program_counter, error))?;
+        let cycles = vip_cycles(chip8, &instruction);
+        self.balance -= cycles as i64;
+        Ok(cycles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FETCH_CYCLES, FRAME_BUDGET, VipTiming, vip_cycles};
+    use crate::{
+        chip8::Chip8,
+        frame::{FrameConfig, FramePacing},
+        instruction::Instruction,
+        quirks::Quirks,
+        rng::FixedSequence,
+    };
+
+    fn new_chip8() -> Chip8<FixedSequence<'static>> {
+        Chip8::with_quirks(FixedSequence::new(&[0]), Quirks::CHIP48)
+    }
+
+    #[test]
+    fn costs_depend_on_machine_state() {
+        let mut chip8 = new_chip8();
+        let cost = |chip8: &Chip8<_>, instruction| vip_cycles(chip8, &instruction) - FETCH_CYCLES;
+
+        assert_eq!(cost(&chip8, Instruction::Ins6XNN(0, 1)), 6);
+        assert_eq!(cost(&chip8, Instruction::Ins3XNN(0, 1)), 10);
+        assert_eq!(cost(&chip8, Instruction::Ins3XNN(0, 0)), 14);
+
+        assert_eq!(cost(&chip8, Instruction::InsDXYN(0, 0, 5)), 26 + 5 * 16);
+        chip8.register_v[0] = 3;
+        assert_eq!(cost(&chip8, Instruction::InsDXYN(0, 0, 5)), 26 + 5 * 28);
+
+        chip8.register_v[0] = 255;
+        assert_eq!(cost(&chip8, Instruction::InsFX33(0)), 80 + 16 * 12);
+    }
+
+    #[test]
+    fn runs_frames_by_cycle_budget() {
+        let mut chip8 = new_chip8();
+        // 0x200 ADD V0, 1, 0x202 JP 0x200
+        chip8.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        let mut config = FrameConfig::new(FramePacing::VipTiming(VipTiming::new()));
+
+        let costs = [FETCH_CYCLES + 10, FETCH_CYCLES + 12];
+        let (mut spent, mut expected) = (0, 0);
+        while spent < FRAME_BUDGET {
+            spent += costs[expected % 2];
+            expected += 1;
+        }
+        let first = chip8.run_frame(&mut config).executed;
+        assert_eq!(first, expected);
+        let second = chip8.run_frame(&mut config).executed;
+        assert!(second.abs_diff(first) <= 1);
+
+        // Blocked machines idle out the rest of the frame
+        chip8.wait_for_vblank = true;
+        assert_eq!(chip8.run_frame(&mut config).executed, 0);
+    }
+}
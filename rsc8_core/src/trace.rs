@@ -0,0 +1,224 @@
+// One line of machine state per tick, taken before the instruction runs.
+// Every field has a fixed width so traces from two runs diff line by line:
+// cycle, PC, opcode, mnemonic, V0..VF, I, SP, DT and ST.
+use crate::{chip8::Chip8, instruction::LONG_OPCODE_PREFIX, rng::RandomSource};
+use core::{
+    fmt::{self, Write},
+    ops::RangeInclusive,
+};
+
+const MNEMONIC_WIDTH: usize = 20;
+
+#[derive(Debug, Clone, Default)]
+pub struct TraceFilter<'a> {
+    pub start_cycle: u64,
+    // Exclusive
+    pub stop_cycle: Option<u64>,
+    // Program counter ranges to trace, empty traces every address
+    pub ranges: &'a [RangeInclusive<u16>],
+}
+
+impl TraceFilter<'_> {
+    pub fn matches(&self, cycle: u64, program_counter: u16) -> bool {
+        cycle >= self.start_cycle
+            && self.stop_cycle.is_none_or(|stop| cycle < stop)
+            && (self.ranges.is_empty()
+                || self
+                    .ranges
+                    .iter()
+                    .any(|range| range.contains(&program_counter)))
+    }
+}
+
+pub struct Tracer<'a> {
+    filter: TraceFilter<'a>,
+    cycle: u64,
+}
+
+impl<'a> Tracer<'a> {
+    pub fn new(filter: TraceFilter<'a>) -> Self {
+        Self { filter, cycle: 0 }
+    }
+
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    // Past the stop cycle nothing more will be written
+    pub fn is_done(&self) -> bool {
+        self.filter
+            .stop_cycle
+            .is_some_and(|stop| self.cycle >= stop)
+    }
+
+    // Call once before every Chip8::tick
+    pub fn trace<R, W, const CAPACITY: usize>(
+        &mut self,
+        chip8: &Chip8<R, CAPACITY>,
+        out: &mut W,
+    ) -> fmt::Result
+    where
+        R: RandomSource,
+        W: Write,
+    {
+        let cycle = self.cycle;
+        self.cycle += 1;
+        if self.filter.matches(cycle, chip8.program_counter) {
+            write_trace_line(out, cycle, chip8)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn write_trace_line<R, W, const CAPACITY: usize>(
+    out: &mut W,
+    cycle: u64,
+    chip8: &Chip8<R, CAPACITY>,
+) -> fmt::Result
+where
+    R: RandomSource,
+    W: Write,
+{
+    write!(out, "{cycle:010} {:04X} ", chip8.program_counter)?;
+
+    // Long instructions show both words, unreadable memory shows dashes
+    let pc = chip8.program_counter as usize;
+    let word = |address: usize| {
+        (address + 1 < chip8.memory_size())
+            .then(|| u16::from_be_bytes([chip8.memory[address], chip8.memory[address + 1]]))
+    };
+    match (word(pc), word(pc + 2)) {
+        (Some(LONG_OPCODE_PREFIX), Some(next)) => {
+            write!(out, "{LONG_OPCODE_PREFIX:04X}{next:04X} ")?
+        }
+        (Some(opcode), _) => write!(out, "{opcode:04X}     ")?,
+        (None, _) => out.write_str("----     ")?,
+    }
+
+    let mut mnemonic = Counter { out, written: 0 };
+    match chip8.peek_instruction() {
+        Ok(instruction) => write!(mnemonic, "{instruction}")?,
+        Err(_) => mnemonic.write_str("???")?,
+    }
+    let padding = MNEMONIC_WIDTH.saturating_sub(mnemonic.written);
+    write!(out, "{:padding$} V", "")?;
+
+    for value in chip8.register_v {
+        write!(out, " {value:02X}")?;
+    }
+    writeln!(
+        out,
+        " I {:04X} SP {:02X} DT {:02X} ST {:02X}",
+        chip8.register_i, chip8.stack_pointer, chip8.delay_timer, chip8.sound_timer
+    )
+}
+
+// Counts what is written so the mnemonic column can be padded
+struct Counter<'a, W> {
+    out: &'a mut W,
+    written: usize,
+}
+
+impl<W: Write> Write for Counter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.written += s.chars().count();
+        self.out.write_str(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TraceFilter, Tracer};
+    use crate::{chip8::Chip8, rng::FixedSequence};
+    use core::fmt::{self, Write};
+
+    struct Buffer {
+        bytes: [u8; 512],
+        len: usize,
+    }
+
+    impl Write for Buffer {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let end = self.len + s.len();
+            self.bytes
+                .get_mut(self.len..end)
+                .ok_or(fmt::Error)?
+                .copy_from_slice(s.as_bytes());
+            self.len = end;
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn as_str(&self) -> &str {
+            core::str::from_utf8(&self.bytes[..self.len]).unwrap()
+        }
+    }
+
+    fn new_chip8() -> Chip8<FixedSequence<'static>> {
+        let mut chip8 = Chip8::new(FixedSequence::new(&[0]));
+        // 0x200 LD V1, 0x2A, 0x202 LD I, 0x1234 (long), 0x206 JP 0x206
+        chip8
+            .load_rom(&[0x61, 0x2A, 0xF0, 0x00, 0x12, 0x34, 0x12, 0x06])
+            .unwrap();
+        chip8
+    }
+
+    #[test]
+    fn writes_fixed_width_lines_before_each_tick() {
+        let mut chip8 = new_chip8();
+        let mut tracer = Tracer::new(TraceFilter::default());
+        let mut buffer = Buffer {
+            bytes: [0; 512],
+            len: 0,
+        };
+        for _ in 0..2 {
+            tracer.trace(&chip8, &mut buffer).unwrap();
+            chip8.tick().unwrap();
+        }
+        let mut lines = buffer.as_str().lines();
+        assert_eq!(
+            lines.next(),
+            Some(
+                "0000000000 0200 612A     LD V1, 0x2A          V 00 00 00 00 00 00 00 00 \
+                 00 00 00 00 00 00 00 00 I 0000 SP 00 DT 00 ST 00"
+            )
+        );
+        assert_eq!(
+            lines.next(),
+            Some(
+                "0000000001 0202 F0001234 LD I, 0x1234         V 00 2A 00 00 00 00 00 00 \
+                 00 00 00 00 00 00 00 00 I 0000 SP 00 DT 00 ST 00"
+            )
+        );
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn filters_by_cycle_and_address() {
+        let ranges = [0x202..=0x205];
+        let filter = TraceFilter {
+            start_cycle: 1,
+            stop_cycle: Some(3),
+            ranges: &ranges,
+        };
+        assert!(!filter.matches(0, 0x202));
+        assert!(filter.matches(1, 0x202));
+        assert!(!filter.matches(1, 0x206));
+        assert!(!filter.matches(3, 0x202));
+
+        let mut tracer = Tracer::new(filter);
+        let mut buffer = Buffer {
+            bytes: [0; 512],
+            len: 0,
+        };
+        let mut chip8 = new_chip8();
+        while !tracer.is_done() {
+            tracer.trace(&chip8, &mut buffer).unwrap();
+            chip8.tick().unwrap();
+        }
+        assert_eq!(tracer.cycle(), 3);
+        assert_eq!(buffer.as_str().lines().count(), 1);
+        assert!(buffer.as_str().starts_with("0000000001 0202"));
+    }
+}
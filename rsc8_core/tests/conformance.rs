@@ -0,0 +1,390 @@
+// Runs the Timendus CHIP-8 test suite v4.2
+// (https://github.com/Timendus/chip8-test-suite) headlessly and reads the
+// results from the screen.
+//
+// Where a ROM draws a checkmark per result, every one is checked on its own:
+// the checkmark must be where the suite draws it, anything else there, like
+// the cross of a failure, fails that line. The places come from the
+// screenshots in img/. The final screen is also compared with a hash in
+// tests/roms/golden.txt, the only check for the ROMs that draw no results.
+//
+// The ROMs are not part of the repository, copy them into tests/roms, see
+// its README. A missing ROM fails the test unless
+// RSC8_CONFORMANCE_ALLOW_MISSING is set. After checking a new screen by eye,
+// record its hash with `RSC8_BLESS=1 cargo test -p rsc8_core --test conformance`.
+// Screens with a failed result are never recorded.
+use rsc8_core::{
+    chip8::Chip8,
+    frame::{FrameConfig, FramePacing},
+    machine::MachineConfig,
+    movie::rom_hash,
+    quirks::Quirks,
+    rng::{FixedSequence, RandomSource},
+};
+use std::{collections::BTreeMap, env, fmt::Write, fs, path::PathBuf};
+
+const TICKS_PER_FRAME: usize = 1000;
+// The suite skips its menus when this byte holds a platform or test number
+const AUTOSTART_ADDRESS: usize = 0x1FF;
+
+// The checkmark the suite draws for a passing result
+const CHECKMARK: [&str; 3] = ["#.#", "##.", "#.."];
+// Results on one line are drawn this many pixels apart
+const RESULT_SPACING: usize = 4;
+
+// `count` results in a row, the first with its top left corner at (x, y)
+struct Results {
+    label: &'static str,
+    x: usize,
+    y: usize,
+    count: usize,
+}
+
+struct SubTest {
+    name: &'static str,
+    rom: &'static str,
+    quirks: Quirks,
+    autostart: Option<u8>,
+    frames: u64,
+    // Keypad bitmasks passed to set_keys from the given frame on
+    keys: &'static [(u64, u16)],
+    results: &'static [Results],
+}
+
+const fn results(label: &'static str, x: usize, y: usize, count: usize) -> Results {
+    Results { label, x, y, count }
+}
+
+const CORAX_RESULTS: [Results; 22] = [
+    results("3X", 11, 2, 1),
+    results("4X", 11, 7, 1),
+    results("5X", 11, 12, 1),
+    results("7X", 11, 17, 1),
+    results("9X", 11, 22, 1),
+    results("1X", 11, 27, 1),
+    results("2X", 27, 2, 1),
+    results("0E", 27, 7, 1),
+    results("80", 27, 12, 1),
+    results("81", 27, 17, 1),
+    results("82", 27, 22, 1),
+    results("83", 27, 27, 1),
+    results("84", 43, 2, 1),
+    results("85", 43, 7, 1),
+    results("87", 43, 12, 1),
+    results("86", 43, 17, 1),
+    results("8E", 43, 22, 1),
+    results("F6", 43, 27, 1),
+    results("F5", 59, 2, 1),
+    results("F3", 59, 7, 1),
+    results("FE", 59, 12, 1),
+    results("vX", 59, 17, 1),
+];
+
+const FLAGS_RESULTS: [Results; 14] = [
+    results("happy 1", 27, 1, 3),
+    results("happy 2", 49, 1, 3),
+    results("happy 3", 5, 6, 3),
+    results("happy 4", 27, 6, 4),
+    results("happy 5", 49, 6, 4),
+    results("happy 6", 5, 11, 3),
+    results("happy 7", 27, 11, 4),
+    results("happy E", 49, 11, 3),
+    results("carry 4", 27, 17, 4),
+    results("carry 5", 49, 17, 4),
+    results("carry 6", 5, 22, 3),
+    results("carry 7", 27, 22, 4),
+    results("carry E", 49, 22, 3),
+    results("other FE", 31, 28, 2),
+];
+
+// The same six lines for every platform
+const QUIRKS_RESULTS: [Results; 6] = [
+    results("vF reset", 59, 2, 1),
+    results("memory", 59, 7, 1),
+    results("display wait", 59, 12, 1),
+    results("clipping", 59, 17, 1),
+    results("shifting", 59, 22, 1),
+    results("jumping", 59, 27, 1),
+];
+
+const SUB_TESTS: [SubTest; 9] = [
+    SubTest {
+        name: "chip8-logo",
+        rom: "1-chip8-logo.ch8",
+        quirks: Quirks::VIP,
+        autostart: None,
+        frames: 60,
+        keys: &[],
+        results: &[],
+    },
+    SubTest {
+        name: "ibm-logo",
+        rom: "2-ibm-logo.ch8",
+        quirks: Quirks::VIP,
+        autostart: None,
+        frames: 60,
+        keys: &[],
+        results: &[],
+    },
+    SubTest {
+        name: "corax+",
+        rom: "3-corax+.ch8",
+        quirks: Quirks::VIP,
+        autostart: None,
+        frames: 60,
+        keys: &[],
+        results: &CORAX_RESULTS,
+    },
+    SubTest {
+        name: "flags",
+        rom: "4-flags.ch8",
+        quirks: Quirks::VIP,
+        autostart: None,
+        frames: 120,
+        keys: &[],
+        results: &FLAGS_RESULTS,
+    },
+    SubTest {
+        name: "quirks-chip8",
+        rom: "5-quirks.ch8",
+        quirks: Quirks::VIP,
+        autostart: Some(1),
+        frames: 600,
+        keys: &[],
+        results: &QUIRKS_RESULTS,
+    },
+    SubTest {
+        name: "quirks-schip",
+        rom: "5-quirks.ch8",
+        quirks: Quirks::SCHIP11,
+        autostart: Some(2),
+        frames: 600,
+        keys: &[],
+        results: &QUIRKS_RESULTS,
+    },
+    SubTest {
+        name: "quirks-xochip",
+        rom: "5-quirks.ch8",
+        quirks: Quirks::XOCHIP,
+        autostart: Some(3),
+        frames: 600,
+        keys: &[],
+        results: &QUIRKS_RESULTS,
+    },
+    // Picks test 3 from the menu, then presses and releases 5 for FX0A
+    SubTest {
+        name: "keypad-fx0a",
+        rom: "6-keypad.ch8",
+        quirks: Quirks::VIP,
+        autostart: None,
+        frames: 180,
+        keys: &[(30, 1 << 0x3), (40, 0), (90, 1 << 0x5), (100, 0)],
+        results: &[results("FX0A getkey", 30, 9, 1)],
+    },
+    SubTest {
+        name: "scrolling-xochip",
+        rom: "8-scrolling.ch8",
+        quirks: Quirks::XOCHIP,
+        autostart: Some(3),
+        frames: 120,
+        keys: &[],
+        results: &[],
+    },
+];
+
+fn roms_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/roms")
+}
+
+fn read_golden() -> BTreeMap<String, u64> {
+    let text = fs::read_to_string(roms_dir().join("golden.txt")).unwrap_or_default();
+    text.lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (name, hash) = line
+                .split_once(' ')
+                .expect("golden line is `<name> <hash>`");
+            let hash = u64::from_str_radix(hash.trim(), 16).expect("golden hash is hex");
+            (name.to_string(), hash)
+        })
+        .collect()
+}
+
+// Runs a sub-test to completion and returns the final machine, or None
+// without the ROM
+fn run(sub_test: &SubTest) -> Option<Chip8<FixedSequence<'static>>> {
+    let rom = fs::read(roms_dir().join(sub_test.rom)).ok()?;
+    let mut chip8 = Chip8::with_quirks(FixedSequence::new(&[0]), sub_test.quirks);
+    if sub_test.quirks == Quirks::XOCHIP {
+        chip8.set_config(MachineConfig::XOCHIP).unwrap();
+    }
+    chip8.load_fontset();
+    chip8.load_rom(&rom).unwrap();
+    if let Some(value) = sub_test.autostart {
+        chip8.memory[AUTOSTART_ADDRESS] = value;
+    }
+    let mut config = FrameConfig::new(FramePacing::Ticks(TICKS_PER_FRAME));
+    for frame in 0..sub_test.frames {
+        if let Some(&(_, keys)) = sub_test.keys.iter().find(|(at, _)| *at == frame) {
+            chip8.set_keys(keys);
+        }
+        if let Some(error) = chip8.run_frame(&mut config).error {
+            panic!("{}: {error}", sub_test.name);
+        }
+    }
+    Some(chip8)
+}
+
+// One character per pixel, by the planes it is set in
+fn render<R: RandomSource>(chip8: &Chip8<R>) -> String {
+    let mut text = String::new();
+    for y in 0..chip8.screen_height() {
+        for x in 0..chip8.screen_width() {
+            text.push(['.', '#', '+', '*'][chip8.screen.pixel(x, y) as usize]);
+        }
+        text.push('\n');
+    }
+    text
+}
+
+// The glyph drawn at (x, y), rows separated by slashes, when it is not the
+// checkmark
+fn check_result(screen: &str, x: usize, y: usize) -> Result<(), String> {
+    let rows: Vec<&str> = screen.lines().skip(y).take(CHECKMARK.len()).collect();
+    let glyph: Vec<&str> = rows
+        .iter()
+        .map(|row| row.get(x..x + CHECKMARK[0].len()).unwrap_or(""))
+        .collect();
+    if glyph == CHECKMARK {
+        Ok(())
+    } else {
+        Err(glyph.join("/"))
+    }
+}
+
+#[test]
+fn timendus_test_suite() {
+    let bless = env::var_os("RSC8_BLESS").is_some();
+    let allow_missing = env::var_os("RSC8_CONFORMANCE_ALLOW_MISSING").is_some();
+    let mut golden = read_golden();
+    let mut report = String::new();
+    let mut failed = false;
+
+    for sub_test in &SUB_TESTS {
+        let Some(chip8) = run(sub_test) else {
+            let status = if allow_missing {
+                "skipped, ROM not found"
+            } else {
+                "MISSING ROM"
+            };
+            writeln!(report, "{:<18} {status}", sub_test.name).unwrap();
+            failed |= !allow_missing;
+            continue;
+        };
+        let screen = render(&chip8);
+
+        let mut results_failed = false;
+        for results in sub_test.results {
+            let mut status = String::from("pass");
+            for index in 0..results.count {
+                let x = results.x + index * RESULT_SPACING;
+                if let Err(glyph) = check_result(&screen, x, results.y) {
+                    status = format!("FAIL, result {} is {glyph}", index + 1);
+                    results_failed = true;
+                    break;
+                }
+            }
+            writeln!(
+                report,
+                "{:<18} {:<14} {status}",
+                sub_test.name, results.label
+            )
+            .unwrap();
+        }
+
+        let hash = rom_hash(screen.as_bytes());
+        let (status, screen_failed) = match golden.get(sub_test.name) {
+            _ if bless && !results_failed => {
+                golden.insert(sub_test.name.to_string(), hash);
+                ("screen recorded", false)
+            }
+            Some(&expected) if expected == hash => ("screen matches", false),
+            Some(_) => ("screen CHANGED", !bless),
+            // The results already decide, the hash waits for a look at the screen
+            None if !sub_test.results.is_empty() => ("screen unrecorded", false),
+            None if allow_missing => ("screen unrecorded, allowed", false),
+            None => ("screen UNRECORDED", !bless),
+        };
+        writeln!(report, "{:<18} {status}", sub_test.name).unwrap();
+        if results_failed || status != "screen matches" {
+            report.push_str(&screen);
+        }
+        failed |= results_failed || screen_failed;
+    }
+
+    if bless {
+        let mut text = String::from("# <sub-test> <screen hash>, see tests/conformance.rs\n");
+        for (name, hash) in &golden {
+            writeln!(text, "{name} {hash:016X}").unwrap();
+        }
+        fs::write(roms_dir().join("golden.txt"), text).unwrap();
+    }
+    eprint!("{report}");
+    assert!(!failed, "conformance failures:\n{report}");
+}
+
+// The final corax+ screen from img/corax-plus.gif, so the reading of the
+// results and the recorded hash are checked without the ROMs
+const CORAX_SCREEN: &str = "\
+................................................................
+..###.#.#.........###.#.#.........###.#.#.........###.###.......
+...##..#...#.#......#..#...#.#....###.###..#.#....#...##...#.#..
+....#.#.#..##.....##..#.#..##.....#.#...#..##.....##....#..##...
+..###.#.#..#......###.#.#..#......###...#..#......#...##...#....
+................................................................
+..#.#.#.#.........###.###.........###.###.........###.###.......
+..###..#...#.#....#.#.##...#.#....###.##...#.#....#....##..#.#..
+....#.#.#..##.....#.#.#....##.....#.#...#..##.....##....#..##...
+....#.#.#..#......###.###..#......###.##...#......#...###..#....
+................................................................
+..###.#.#.........###.###.........###.###.........###.###.......
+..##...#...#.#....###.#.#..#.#....###...#..#.#....#...##...#.#..
+....#.#.#..##.....#.#.#.#..##.....#.#..#...##.....##..#....##...
+..##..#.#..#......###.###..#......###..#...#......#...###..#....
+................................................................
+..###.#.#.........###.##..........###..##.............#.#.......
+....#..#...#.#....###..#...#.#....###.#....#.#....#.#..#...#.#..
+...#..#.#..##.....#.#..#...##.....#.#.###..##.....#.#.#.#..##...
+...#..#.#..#......###.###..#......###.###..#.......#..#.#..#....
+................................................................
+..###.#.#.........###.###.........###.###.......................
+..###..#...#.#....###...#..#.#....###.##...#.#..................
+....#.#.#..##.....#.#.##...##.....#.#.#....##...................
+..##..#.#..#......###.###..#......###.###..#....................
+................................................................
+..##..#.#.........###.###.........###..##.............#.#...###.
+...#...#...#.#....###..##..#.#....#...#....#.#....#.#.###.....#.
+...#..#.#..##.....#.#...#..##.....##..###..##.....#.#...#...##..
+..###.#.#..#......###.###..#......#...###..#.......#....#.#.###.
+................................................................
+................................................................
+";
+
+#[test]
+fn reads_results_from_the_screen() {
+    for results in &CORAX_RESULTS {
+        assert_eq!(check_result(CORAX_SCREEN, results.x, results.y), Ok(()));
+    }
+    assert_eq!(
+        check_result(CORAX_SCREEN, 2, 1),
+        Err("###/.##/..#".to_string())
+    );
+    assert_eq!(
+        check_result(CORAX_SCREEN, 59, 22),
+        Err(".../.../...".to_string())
+    );
+    assert_eq!(
+        read_golden().get("corax+"),
+        Some(&rom_hash(CORAX_SCREEN.as_bytes()))
+    );
+}
@@ -0,0 +1,102 @@
+use rsc8_core::{machine::MachineConfig, quirks::Quirks};
+use std::env;
+
+const USAGE: &str = "Usage: rsc8_headless [--quirks <vip|chip48|schip|xochip>] \
+[--machine <chip8|2k|eti660|xochip>] [--frames <n>] [--ticks <n> | --vip-timing] [--seed <n>] \
+[--keys <file>] [--screen <file>] [--registers <file>] [--memory <file>] [--profile <file>] \
+[--profile-listing <file>] <your_rom.ch8 | source.8o>";
+
+pub struct Args {
+    pub rom_path: String,
+    pub quirks: Quirks,
+    pub config: MachineConfig,
+    pub frames: u64,
+    pub ticks_per_frame: usize,
+    pub vip_timing: bool,
+    pub seed: Option<u16>,
+    pub keys_path: Option<String>,
+    pub screen_path: Option<String>,
+    pub registers_path: Option<String>,
+    pub memory_path: Option<String>,
+    pub profile_path: Option<String>,
+    pub profile_listing_path: Option<String>,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, String> {
+        let mut rom_path = None;
+        let mut quirks = Quirks::default();
+        let mut config = None;
+        let mut frames = 60;
+        let mut ticks_per_frame = None;
+        let mut vip_timing = false;
+        let mut seed = None;
+        let mut keys_path = None;
+        let mut screen_path = None;
+        let mut registers_path = None;
+        let mut memory_path = None;
+        let mut profile_path = None;
+        let mut profile_listing_path = None;
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--quirks" => {
+                    let name = args.next().ok_or(USAGE)?;
+                    quirks = Quirks::from_name(&name)
+                        .ok_or_else(|| format!("Unknown quirks profile: {name}"))?;
+                }
+                "--machine" => {
+                    let name = args.next().ok_or(USAGE)?;
+                    config = Some(
+                        MachineConfig::from_name(&name)
+                            .ok_or_else(|| format!("Unknown machine: {name}"))?,
+                    );
+                }
+                "--frames" => frames = parse_number("frame count", args.next().ok_or(USAGE)?)?,
+                "--ticks" => {
+                    ticks_per_frame = Some(parse_number("tick count", args.next().ok_or(USAGE)?)?)
+                }
+                "--vip-timing" => vip_timing = true,
+                "--seed" => seed = Some(parse_number("seed", args.next().ok_or(USAGE)?)?),
+                "--keys" => keys_path = Some(args.next().ok_or(USAGE)?),
+                "--screen" => screen_path = Some(args.next().ok_or(USAGE)?),
+                "--registers" => registers_path = Some(args.next().ok_or(USAGE)?),
+                "--memory" => memory_path = Some(args.next().ok_or(USAGE)?),
+                "--profile" => profile_path = Some(args.next().ok_or(USAGE)?),
+                "--profile-listing" => profile_listing_path = Some(args.next().ok_or(USAGE)?),
+                _ if arg.starts_with("--") => return Err(format!("Unknown option: {arg}")),
+                _ => rom_path = Some(arg),
+            }
+        }
+
+        if vip_timing && ticks_per_frame.is_some() {
+            return Err("--ticks and --vip-timing cannot be used together".into());
+        }
+
+        Ok(Self {
+            rom_path: rom_path.ok_or(USAGE)?,
+            quirks,
+            // XO-CHIP programs address the full 64 KiB
+            config: config.unwrap_or(if quirks == Quirks::XOCHIP {
+                MachineConfig::XOCHIP
+            } else {
+                MachineConfig::CHIP8
+            }),
+            frames,
+            ticks_per_frame: ticks_per_frame.unwrap_or(8),
+            vip_timing,
+            seed,
+            keys_path,
+            screen_path,
+            registers_path,
+            memory_path,
+            profile_path,
+            profile_listing_path,
+        })
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, text: String) -> Result<T, String> {
+    text.parse().map_err(|_| format!("Invalid {name}: {text}"))
+}
@@ -0,0 +1,55 @@
+use rsc8_core::{chip8::Chip8, rng::RandomSource};
+use std::io::{self, Write};
+
+// Indexed by the plane bits of a pixel
+const PIXEL_CHARS: [char; 4] = ['.', '#', '+', '@'];
+const HEX_DUMP_WIDTH: usize = 16;
+
+// The visible screen, one line per row
+pub fn write_screen<R>(out: &mut impl Write, chip8: &Chip8<R>) -> io::Result<()>
+where
+    R: RandomSource,
+{
+    for y in 0..chip8.screen_height() {
+        let row = (0..chip8.screen_width())
+            .map(|x| PIXEL_CHARS[chip8.screen.pixel(x, y) as usize])
+            .collect::<String>();
+        writeln!(out, "{row}")?;
+    }
+    Ok(())
+}
+
+pub fn write_registers<R>(out: &mut impl Write, chip8: &Chip8<R>) -> io::Result<()>
+where
+    R: RandomSource,
+{
+    writeln!(out, "PC {:04X}", chip8.program_counter)?;
+    writeln!(out, "I {:04X}", chip8.register_i)?;
+    for (index, value) in chip8.register_v.iter().enumerate() {
+        writeln!(out, "V{index:X} {value:02X}")?;
+    }
+    writeln!(out, "SP {:02X}", chip8.stack_pointer)?;
+    write!(out, "STACK")?;
+    for address in chip8.stack.iter().take(chip8.stack_pointer as usize) {
+        write!(out, " {address:04X}")?;
+    }
+    writeln!(out)?;
+    writeln!(out, "DT {:02X}", chip8.delay_timer)?;
+    writeln!(out, "ST {:02X}", chip8.sound_timer)
+}
+
+// Addressable memory as a hex dump, for terminals
+pub fn write_memory_hex<R>(out: &mut impl Write, chip8: &Chip8<R>) -> io::Result<()>
+where
+    R: RandomSource,
+{
+    let memory = chip8.memory();
+    for (line, bytes) in memory.chunks(HEX_DUMP_WIDTH).enumerate() {
+        write!(out, "{:04X}:", line * HEX_DUMP_WIDTH)?;
+        for byte in bytes {
+            write!(out, " {byte:02X}")?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+// Scripted keypad input, one change per line: `<frame> <keys>`, where keys
+// are the hex keypad digits held from that frame on, or `-` for none.
+// Everything after `#` is a comment.
+//
+//     # press 5 on frame 30, release it on frame 40
+//     30 5
+//     40 -
+pub struct KeySchedule {
+    // Sorted by frame
+    changes: Vec<(u64, u16)>,
+    next: usize,
+}
+
+impl KeySchedule {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut changes = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+            let Some(frame) = fields.next() else {
+                continue;
+            };
+            let invalid = |what: &str| format!("line {}: invalid {what}", index + 1);
+            let frame = frame.parse::<u64>().map_err(|_| invalid("frame"))?;
+            let mut keypad = 0;
+            for key in fields.filter(|&key| key != "-") {
+                let key = u8::from_str_radix(key, 16)
+                    .ok()
+                    .filter(|&key| key < 16)
+                    .ok_or_else(|| invalid("key"))?;
+                keypad |= 1 << key;
+            }
+            changes.push((frame, keypad));
+        }
+        // Later lines win for the same frame
+        changes.sort_by_key(|&(frame, _)| frame);
+        Ok(Self { changes, next: 0 })
+    }
+
+    // Keypad state to apply at this frame, if it changes. Frames must be
+    // asked for in increasing order.
+    pub fn keypad_at(&mut self, frame: u64) -> Option<u16> {
+        let mut keypad = None;
+        while let Some(&(at, state)) = self.changes.get(self.next)
+            && at <= frame
+        {
+            keypad = Some(state);
+            self.next += 1;
+        }
+        keypad
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KeySchedule;
+
+    #[test]
+    fn parses_keys_held_from_each_frame() {
+        let mut schedule =
+            KeySchedule::parse("# comment\n30 5 a\n\n40 -  # release\n10 F\n").unwrap();
+        assert_eq!(schedule.keypad_at(0), None);
+        assert_eq!(schedule.keypad_at(10), Some(1 << 0xF));
+        assert_eq!(schedule.keypad_at(20), None);
+        assert_eq!(schedule.keypad_at(35), Some(1 << 5 | 1 << 0xA));
+        assert_eq!(schedule.keypad_at(50), Some(0));
+
+        assert_eq!(
+            KeySchedule::parse("1 10").err(),
+            Some("line 1: invalid key".into())
+        );
+        assert_eq!(
+            KeySchedule::parse("x 1").err(),
+            Some("line 1: invalid frame".into())
+        );
+    }
+}
@@ -0,0 +1,163 @@
+mod args;
+mod dump;
+mod keys;
+
+use args::Args;
+use keys::KeySchedule;
+use rsc8_asm::assemble;
+use rsc8_core::{
+    analysis::analyze,
+    chip8::Chip8,
+    disassembler::Syntax,
+    error::ExecutionError,
+    frame::{FrameConfig, FramePacing},
+    idle::RunState,
+    profile::Profile,
+    rng::LinearCongruentialGenerator,
+    timing::VipTiming,
+};
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    process::ExitCode,
+};
+
+// Usage and I/O errors exit with 1
+const EXIT_INSTRUCTION_ERROR: u8 = 2;
+
+fn main() -> Result<ExitCode, Box<dyn Error>> {
+    let args = Args::parse()?;
+
+    // Read rom, assembling Octo sources first
+    let mut rom = fs::read(&args.rom_path)?;
+    if args.rom_path.ends_with(".8o") {
+        let source = String::from_utf8(rom)?;
+        rom = assemble(&source).map_err(|error| format!("{}:{error}", args.rom_path))?;
+    }
+
+    let mut keys = match &args.keys_path {
+        Some(path) => Some(
+            KeySchedule::parse(&fs::read_to_string(path)?)
+                .map_err(|error| format!("{path}: {error}"))?,
+        ),
+        None => None,
+    };
+
+    // Init chip8, with a fixed seed so runs are reproducible
+    let rng = match args.seed {
+        Some(seed) => LinearCongruentialGenerator { seed },
+        None => LinearCongruentialGenerator::default(),
+    };
+    let mut chip8 = Chip8::with_config(rng, args.quirks, args.config)?;
+    chip8.enable_decode_cache();
+    chip8.load_fontset();
+    chip8.load_rom(&rom)?;
+
+    let mut profile = (args.profile_path.is_some() || args.profile_listing_path.is_some())
+        .then(|| Profile::new(chip8.memory_size()));
+
+    // The machine state is dumped even when the program fails
+    let result = run(&mut chip8, &args, keys.as_mut(), profile.as_mut());
+    dump(&chip8, &args)?;
+    if let Some(profile) = &profile {
+        write_profile(profile, &args, &rom)?;
+    }
+    match result {
+        Ok(()) => Ok(ExitCode::SUCCESS),
+        Err((frame, error)) => {
+            eprintln!("Frame {frame}: {error}");
+            Ok(ExitCode::from(EXIT_INSTRUCTION_ERROR))
+        }
+    }
+}
+
+fn run(
+    chip8: &mut Chip8<LinearCongruentialGenerator>,
+    args: &Args,
+    mut keys: Option<&mut KeySchedule>,
+    mut profile: Option<&mut Profile>,
+) -> Result<(), (u64, ExecutionError)> {
+    // Either a fixed instruction count or the VIP cycle budget
+    let mut config = FrameConfig {
+        skip_idle_loops: true,
+        ..FrameConfig::new(if args.vip_timing {
+            FramePacing::VipTiming(VipTiming::new())
+        } else {
+            FramePacing::Ticks(args.ticks_per_frame)
+        })
+    };
+    for frame in 0..args.frames {
+        if let Some(keypad) = keys.as_deref_mut().and_then(|keys| keys.keypad_at(frame)) {
+            chip8.set_keys(keypad);
+        }
+        let report = match profile.as_deref_mut() {
+            Some(profile) => chip8.run_frame_with(&mut config, |chip8| profile.record(chip8)),
+            None => chip8.run_frame(&mut config),
+        };
+        if let Some(error) = report.error {
+            return Err((frame, error));
+        }
+        // Exited, or stuck in a loop that no timer or key can end
+        if report.state == RunState::Halted {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn write_profile(profile: &Profile, args: &Args, rom: &[u8]) -> Result<(), Box<dyn Error>> {
+    if let Some(path) = &args.profile_path {
+        let mut json = String::new();
+        profile.write_json(&mut json)?;
+        fs::write(path, json)?;
+    }
+    if let Some(path) = &args.profile_listing_path {
+        let disassembly = analyze(rom, args.config.program_start)?.disassemble(Syntax::Standard);
+        fs::write(path, profile.annotate(&disassembly).to_string())?;
+    }
+    Ok(())
+}
+
+// Parts without an output file go to stdout, in order
+fn dump(chip8: &Chip8<LinearCongruentialGenerator>, args: &Args) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let mut separate = false;
+    let mut section = |stdout: &mut io::StdoutLock| {
+        let result = if separate { writeln!(stdout) } else { Ok(()) };
+        separate = true;
+        result
+    };
+
+    match &args.screen_path {
+        Some(path) => {
+            let mut file = BufWriter::new(File::create(path)?);
+            dump::write_screen(&mut file, chip8)?;
+            file.flush()?;
+        }
+        None => {
+            section(&mut stdout)?;
+            dump::write_screen(&mut stdout, chip8)?;
+        }
+    }
+    match &args.registers_path {
+        Some(path) => {
+            let mut file = BufWriter::new(File::create(path)?);
+            dump::write_registers(&mut file, chip8)?;
+            file.flush()?;
+        }
+        None => {
+            section(&mut stdout)?;
+            dump::write_registers(&mut stdout, chip8)?;
+        }
+    }
+    match &args.memory_path {
+        Some(path) => fs::write(path, chip8.memory())?,
+        None => {
+            section(&mut stdout)?;
+            dump::write_memory_hex(&mut stdout, chip8)?;
+        }
+    }
+    stdout.flush()
+}
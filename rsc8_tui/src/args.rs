@@ -0,0 +1,164 @@
+use rsc8_core::{disassembler::Syntax, fontset::Fontset, machine::MachineConfig, quirks::Quirks};
+use std::{env, ops::RangeInclusive};
+
+const USAGE: &str = "Usage: rsc8_tui [--quirks <vip|chip48|schip|xochip>] [--machine <chip8|2k|eti660|xochip>] \
+[--font <octo|vip|dream6800|eti660|fishnchips|file>] [--big-font <octo|schip>] \
+[--record <file> | --replay <file>] [--disassemble [octo]] [--dot [calls]] [--gdb <port>] [--vip-timing] \
+[--trace <file> [--trace-range <start>-<end>]... [--trace-start <cycle>] [--trace-stop <cycle>]] <your_rom.ch8 | source.8o>";
+
+// Graphs --dot prints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Graph {
+    ControlFlow,
+    Calls,
+}
+
+pub struct Args {
+    pub rom_path: String,
+    pub quirks: Quirks,
+    pub config: MachineConfig,
+    pub fontset: Fontset,
+    // Raw font bytes read over fontset
+    pub font_path: Option<String>,
+    pub record_path: Option<String>,
+    pub replay_path: Option<String>,
+    pub disassemble: Option<Syntax>,
+    pub dot: Option<Graph>,
+    pub gdb_port: Option<u16>,
+    pub vip_timing: bool,
+    pub trace_path: Option<String>,
+    pub trace_ranges: Vec<RangeInclusive<u16>>,
+    pub trace_start: u64,
+    pub trace_stop: Option<u64>,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, String> {
+        let mut rom_path = None;
+        let mut quirks = Quirks::default();
+        let mut config = None;
+        let mut fontset = Fontset::default();
+        let mut font_path = None;
+        let mut record_path = None;
+        let mut replay_path = None;
+        let mut disassemble = None;
+        let mut dot = None;
+        let mut gdb_port = None;
+        let mut vip_timing = false;
+        let mut trace_path = None;
+        let mut trace_ranges = Vec::new();
+        let mut trace_start = 0;
+        let mut trace_stop = None;
+
+        let mut args = env::args().skip(1).peekable();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--quirks" => {
+                    let name = args.next().ok_or(USAGE)?;
+                    quirks = Quirks::from_name(&name)
+                        .ok_or_else(|| format!("Unknown quirks profile: {name}"))?;
+                }
+                "--machine" => {
+                    let name = args.next().ok_or(USAGE)?;
+                    config = Some(
+                        MachineConfig::from_name(&name)
+                            .ok_or_else(|| format!("Unknown machine: {name}"))?,
+                    );
+                }
+                "--font" => {
+                    let font = args.next().ok_or(USAGE)?;
+                    match Fontset::small_font_from_name(&font) {
+                        Some(small) => fontset.small = small,
+                        None => font_path = Some(font),
+                    }
+                }
+                "--big-font" => {
+                    let name = args.next().ok_or(USAGE)?;
+                    fontset.big = Fontset::big_font_from_name(&name)
+                        .ok_or_else(|| format!("Unknown big font: {name}"))?;
+                }
+                "--record" => record_path = Some(args.next().ok_or(USAGE)?),
+                "--replay" => replay_path = Some(args.next().ok_or(USAGE)?),
+                "--disassemble" => {
+                    disassemble = Some(if args.next_if_eq("octo").is_some() {
+                        Syntax::Octo
+                    } else {
+                        Syntax::Standard
+                    });
+                }
+                "--dot" => {
+                    dot = Some(if args.next_if_eq("calls").is_some() {
+                        Graph::Calls
+                    } else {
+                        Graph::ControlFlow
+                    });
+                }
+                "--gdb" => {
+                    let port = args.next().ok_or(USAGE)?;
+                    gdb_port = Some(
+                        port.parse()
+                            .map_err(|_| format!("Invalid gdb port: {port}"))?,
+                    );
+                }
+                "--vip-timing" => vip_timing = true,
+                "--trace" => trace_path = Some(args.next().ok_or(USAGE)?),
+                "--trace-range" => {
+                    let range = args.next().ok_or(USAGE)?;
+                    let invalid = || format!("Invalid trace range: {range}");
+                    let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+                    let start = parse_address(start).ok_or_else(invalid)?;
+                    let end = parse_address(end).ok_or_else(invalid)?;
+                    trace_ranges.push(start..=end);
+                }
+                "--trace-start" => trace_start = parse_cycle(args.next().ok_or(USAGE)?)?,
+                "--trace-stop" => trace_stop = Some(parse_cycle(args.next().ok_or(USAGE)?)?),
+                _ if arg.starts_with("--") => return Err(format!("Unknown option: {arg}")),
+                _ => rom_path = Some(arg),
+            }
+        }
+
+        if record_path.is_some() && replay_path.is_some() {
+            return Err("--record and --replay cannot be used together".into());
+        }
+        if trace_path.is_some() && gdb_port.is_some() {
+            return Err("--trace and --gdb cannot be used together".into());
+        }
+        // The debugger runs a fixed number of instructions per frame
+        if vip_timing && gdb_port.is_some() {
+            return Err("--vip-timing and --gdb cannot be used together".into());
+        }
+
+        Ok(Self {
+            rom_path: rom_path.ok_or(USAGE)?,
+            quirks,
+            // XO-CHIP programs address the full 64 KiB
+            config: config.unwrap_or(if quirks == Quirks::XOCHIP {
+                MachineConfig::XOCHIP
+            } else {
+                MachineConfig::CHIP8
+            }),
+            fontset,
+            font_path,
+            record_path,
+            replay_path,
+            disassemble,
+            dot,
+            gdb_port,
+            vip_timing,
+            trace_path,
+            trace_ranges,
+            trace_start,
+            trace_stop,
+        })
+    }
+}
+
+// Addresses are hexadecimal, with or without a 0x prefix
+fn parse_address(text: &str) -> Option<u16> {
+    u16::from_str_radix(text.trim_start_matches("0x"), 16).ok()
+}
+
+fn parse_cycle(text: String) -> Result<u64, String> {
+    text.parse()
+        .map_err(|_| format!("Invalid trace cycle: {text}"))
+}
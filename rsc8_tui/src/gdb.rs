@@ -0,0 +1,610 @@
+// GDB remote serial protocol stub, see
+// https://sourceware.org/gdb/current/onlinedocs/gdb.html/Remote-Protocol.html
+use rsc8_core::{
+    chip8::{Chip8, NUM_REGISTERS},
+    debugger::{Debugger, StopReason, WatchKind, WatchTarget, Watchpoint},
+    rng::RandomSource,
+};
+use std::{
+    fmt::Write as _,
+    io::{self, ErrorKind, Read, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+};
+
+// V0..VF, then I, PC, SP, DT and ST
+const REGISTER_I: usize = NUM_REGISTERS;
+const REGISTER_PC: usize = NUM_REGISTERS + 1;
+const REGISTER_SP: usize = NUM_REGISTERS + 2;
+const REGISTER_DT: usize = NUM_REGISTERS + 3;
+const REGISTER_ST: usize = NUM_REGISTERS + 4;
+const REGISTER_COUNT: usize = NUM_REGISTERS + 5;
+const INTERRUPT: u8 = 0x03;
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Halted,
+    Running,
+    Stepping,
+}
+
+pub struct GdbServer {
+    stream: Option<TcpStream>,
+    buffer: Vec<u8>,
+    session: Session,
+}
+
+impl GdbServer {
+    // Blocks until a debugger attaches, the machine starts halted
+    pub fn accept(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        eprintln!("Waiting for gdb on {}", listener.local_addr()?);
+        let (stream, _) = listener.accept()?;
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        Ok(Self {
+            stream: Some(stream),
+            buffer: Vec::new(),
+            session: Session::new(),
+        })
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.session.state == State::Halted
+    }
+
+    pub fn is_killed(&self) -> bool {
+        self.session.killed
+    }
+
+    // Handles every packet that has arrived since the last call
+    pub fn poll<R>(&mut self, chip8: &mut Chip8<R>) -> io::Result<()>
+    where
+        R: RandomSource,
+    {
+        let Some(stream) = self.stream.as_mut() else {
+            return Ok(());
+        };
+        let mut chunk = [0; 1024];
+        loop {
+            match stream.read(&mut chunk) {
+                Ok(read) if read > 0 => self.buffer.extend_from_slice(&chunk[..read]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Ok(_) => {
+                    self.disconnect();
+                    return Ok(());
+                }
+                Err(error) if error.kind() == ErrorKind::ConnectionReset => {
+                    self.disconnect();
+                    return Ok(());
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        while let Some(packet) = self.next_packet()? {
+            let reply = match packet {
+                Packet::Interrupt => self.session.interrupt(),
+                Packet::Command(command) => self.session.handle(chip8, &command),
+            };
+            if let Some(reply) = reply {
+                self.send(&reply)?;
+            }
+            if self.session.detached {
+                self.disconnect();
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // Runs up to `budget` instructions unless halted, reporting any stop to gdb
+    pub fn run<R>(&mut self, chip8: &mut Chip8<R>, budget: usize) -> io::Result<()>
+    where
+        R: RandomSource,
+    {
+        if let Some(reply) = self.session.run(chip8, budget) {
+            self.send(&reply)?;
+        }
+        Ok(())
+    }
+
+    fn disconnect(&mut self) {
+        self.stream = None;
+        self.session = Session::new();
+        self.session.state = State::Running;
+    }
+
+    fn next_packet(&mut self) -> io::Result<Option<Packet>> {
+        loop {
+            match self.buffer.first() {
+                None => return Ok(None),
+                Some(&INTERRUPT) => {
+                    self.buffer.remove(0);
+                    return Ok(Some(Packet::Interrupt));
+                }
+                Some(b'$') => break,
+                // Acknowledgements and line noise
+                Some(_) => {
+                    self.buffer.remove(0);
+                }
+            }
+        }
+        let Some(end) = self.buffer.iter().position(|&byte| byte == b'#') else {
+            return Ok(None);
+        };
+        if self.buffer.len() < end + 3 {
+            return Ok(None);
+        }
+        let packet: Vec<u8> = self.buffer.drain(..end + 3).collect();
+        let payload = &packet[1..end];
+        let checksum = std::str::from_utf8(&packet[end + 1..])
+            .ok()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if checksum != Some(checksum_of(payload)) {
+            self.write_raw(b"-")?;
+            return self.next_packet();
+        }
+        self.write_raw(b"+")?;
+        Ok(Some(Packet::Command(
+            String::from_utf8_lossy(payload).into_owned(),
+        )))
+    }
+
+    fn send(&mut self, payload: &str) -> io::Result<()> {
+        let packet = format!("${payload}#{:02x}", checksum_of(payload.as_bytes()));
+        self.write_raw(packet.as_bytes())
+    }
+
+    fn write_raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let Some(stream) = self.stream.as_mut() else {
+            return Ok(());
+        };
+        stream.set_nonblocking(false)?;
+        let result = stream.write_all(bytes);
+        stream.set_nonblocking(true)?;
+        result
+    }
+}
+
+enum Packet {
+    Interrupt,
+    Command(String),
+}
+
+struct Session {
+    debugger: Debugger,
+    state: State,
+    killed: bool,
+    detached: bool,
+}
+
+impl Session {
+    fn new() -> Self {
+        Self {
+            debugger: Debugger::new(),
+            state: State::Halted,
+            killed: false,
+            detached: false,
+        }
+    }
+
+    fn interrupt(&mut self) -> Option<String> {
+        if self.state == State::Halted {
+            return None;
+        }
+        self.state = State::Halted;
+        Some(signal(SIGINT))
+    }
+
+    fn run<R>(&mut self, chip8: &mut Chip8<R>, budget: usize) -> Option<String>
+    where
+        R: RandomSource,
+    {
+        let result = match self.state {
+            State::Halted => return None,
+            State::Running => self.debugger.resume(chip8, budget),
+            State::Stepping => self.debugger.step(chip8),
+        };
+        let reply = match result {
+            Ok(StopReason::BudgetExhausted | StopReason::Blocked) => return None,
+            Ok(StopReason::Exited) => "W00".to_string(),
+            Ok(StopReason::Watchpoint {
+                watchpoint:
+                    Watchpoint {
+                        target: WatchTarget::Memory { start, .. },
+                        kind,
+                    },
+                ..
+            }) => {
+                let name = match kind {
+                    WatchKind::Write => "watch",
+                    WatchKind::Read => "rwatch",
+                    WatchKind::ReadWrite => "awatch",
+                };
+                format!("T{SIGTRAP:02x}{name}:{start:x};")
+            }
+            Ok(_) => signal(SIGTRAP),
+            Err(_) => signal(SIGILL),
+        };
+        self.state = State::Halted;
+        Some(reply)
+    }
+
+    // Returns the reply to a command, an empty reply means unsupported. Resuming
+    // has no reply until execution stops again.
+    fn handle<R>(&mut self, chip8: &mut Chip8<R>, command: &str) -> Option<String>
+    where
+        R: RandomSource,
+    {
+        let kind = command.get(..1).unwrap_or_default();
+        let arguments = command.get(1..).unwrap_or_default();
+        let reply = match kind {
+            "?" => Some(signal(SIGTRAP)),
+            "g" => Some(read_registers(chip8)),
+            "G" => write_registers(chip8, arguments).map(|_| ok()),
+            "p" => usize::from_str_radix(arguments, 16)
+                .ok()
+                .and_then(|register| read_register(chip8, register)),
+            "P" => arguments.split_once('=').and_then(|(register, value)| {
+                let register = usize::from_str_radix(register, 16).ok()?;
+                write_register(chip8, register, &decode_hex(value)?).map(|_| ok())
+            }),
+            "m" => read_memory(chip8, arguments),
+            "M" => write_memory(chip8, arguments).map(|_| ok()),
+            "c" | "s" => {
+                if !arguments.is_empty() {
+                    // A bad address is an error, not a resume from 0x000
+                    let Ok(address) = u16::from_str_radix(arguments, 16) else {
+                        return Some("E01".to_string());
+                    };
+                    chip8.program_counter = address;
+                }
+                self.state = if kind == "c" {
+                    State::Running
+                } else {
+                    State::Stepping
+                };
+                return None;
+            }
+            "Z" | "z" => self.update_point(kind == "Z", arguments),
+            "D" => {
+                self.detached = true;
+                Some(ok())
+            }
+            "k" => {
+                self.killed = true;
+                Some(ok())
+            }
+            "H" => Some(ok()),
+            "q" => query(command),
+            _ => Some(String::new()),
+        };
+        Some(reply.unwrap_or_else(|| "E01".to_string()))
+    }
+
+    // Z0 is a breakpoint, Z2, Z3 and Z4 are write, read and access watchpoints
+    fn update_point(&mut self, insert: bool, arguments: &str) -> Option<String> {
+        let mut fields = arguments.split(',');
+        let kind = fields.next()?;
+        let address = u16::from_str_radix(fields.next()?, 16).ok()?;
+        let length = u16::from_str_radix(fields.next()?, 16).ok()?.max(1);
+        let watch_kind = match kind {
+            "0" => {
+                if insert {
+                    self.debugger.add_breakpoint(address).ok()?;
+                } else {
+                    self.debugger.remove_breakpoint(address);
+                }
+                return Some(ok());
+            }
+            "2" => WatchKind::Write,
+            "3" => WatchKind::Read,
+            "4" => WatchKind::ReadWrite,
+            _ => return Some(String::new()),
+        };
+        let watchpoint = Watchpoint {
+            target: WatchTarget::Memory {
+                start: address,
+                end: address.checked_add(length - 1)?,
+            },
+            kind: watch_kind,
+        };
+        if insert {
+            self.debugger.add_watchpoint(watchpoint).ok()?;
+        } else {
+            self.debugger.remove_watchpoint(watchpoint);
+        }
+        Some(ok())
+    }
+}
+
+fn query(command: &str) -> Option<String> {
+    if command.starts_with("qSupported") {
+        return Some("PacketSize=1000;qXfer:features:read+".to_string());
+    }
+    if let Some(range) = command.strip_prefix("qXfer:features:read:target.xml:") {
+        let (offset, length) = range.split_once(',')?;
+        let offset = usize::from_str_radix(offset, 16).ok()?;
+        let length = usize::from_str_radix(length, 16).ok()?;
+        let description = target_description();
+        let chunk = description.get(offset..).unwrap_or_default();
+        let chunk = &chunk[..chunk.len().min(length)];
+        let more = offset + chunk.len() < description.len();
+        return Some(format!("{}{chunk}", if more { 'm' } else { 'l' }));
+    }
+    Some(
+        match command {
+            "qAttached" => "1",
+            "qC" => "QC1",
+            "qfThreadInfo" => "m1",
+            "qsThreadInfo" => "l",
+            _ => "",
+        }
+        .to_string(),
+    )
+}
+
+fn target_description() -> String {
+    let mut registers = String::new();
+    for index in 0..NUM_REGISTERS {
+        let _ = writeln!(
+            registers,
+            "    <reg name=\"v{index:x}\" bitsize=\"8\" type=\"uint8\"/>"
+        );
+    }
+    format!(
+        "<?xml version=\"1.0\"?>
+<!DOCTYPE target SYSTEM \"gdb-target.dtd\">
+<target version=\"1.0\">
+  <feature name=\"org.rsc8.chip8\">
+{registers}    <reg name=\"i\" bitsize=\"16\" type=\"data_ptr\"/>
+    <reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>
+    <reg name=\"sp\" bitsize=\"8\" type=\"uint8\"/>
+    <reg name=\"dt\" bitsize=\"8\" type=\"uint8\"/>
+    <reg name=\"st\" bitsize=\"8\" type=\"uint8\"/>
+  </feature>
+</target>
+"
+    )
+}
+
+// Multi-byte registers are little endian
+fn read_register<R>(chip8: &Chip8<R>, register: usize) -> Option<String>
+where
+    R: RandomSource,
+{
+    let bytes = match register {
+        0..NUM_REGISTERS => vec![chip8.register_v[register]],
+        REGISTER_I => chip8.register_i.to_le_bytes().to_vec(),
+        REGISTER_PC => chip8.program_counter.to_le_bytes().to_vec(),
+        REGISTER_SP => vec![chip8.stack_pointer],
+        REGISTER_DT => vec![chip8.delay_timer],
+        REGISTER_ST => vec![chip8.sound_timer],
+        _ => return None,
+    };
+    Some(encode_hex(&bytes))
+}
+
+fn write_register<R>(chip8: &mut Chip8<R>, register: usize, bytes: &[u8]) -> Option<()>
+where
+    R: RandomSource,
+{
+    let word = || Some(u16::from_le_bytes(bytes.try_into().ok()?));
+    let byte = || (bytes.len() == 1).then(|| bytes[0]);
+    match register {
+        0..NUM_REGISTERS => chip8.register_v[register] = byte()?,
+        REGISTER_I => chip8.register_i = word()?,
+        REGISTER_PC => chip8.program_counter = word()?,
+        REGISTER_SP => chip8.stack_pointer = byte()?.min(chip8.stack.len() as u8),
+        REGISTER_DT => chip8.delay_timer = byte()?,
+        REGISTER_ST => chip8.sound_timer = byte()?,
+        _ => return None,
+    }
+    Some(())
+}
+
+fn read_registers<R>(chip8: &Chip8<R>) -> String
+where
+    R: RandomSource,
+{
+    (0..REGISTER_COUNT)
+        .filter_map(|register| read_register(chip8, register))
+        .collect()
+}
+
+fn write_registers<R>(chip8: &mut Chip8<R>, hex: &str) -> Option<()>
+where
+    R: RandomSource,
+{
+    let bytes = decode_hex(hex)?;
+    let mut offset = 0;
+    for register in 0..REGISTER_COUNT {
+        let size = if matches!(register, REGISTER_I | REGISTER_PC) {
+            2
+        } else {
+            1
+        };
+        write_register(chip8, register, bytes.get(offset..offset + size)?)?;
+        offset += size;
+    }
+    Some(())
+}
+
+fn memory_range<R>(chip8: &Chip8<R>, range: &str) -> Option<(usize, usize)>
+where
+    R: RandomSource,
+{
+    let (address, length) = range.split_once(',')?;
+    let address = usize::from_str_radix(address, 16).ok()?;
+    let length = usize::from_str_radix(length, 16).ok()?;
+    let end = address.checked_add(length)?;
+    (end <= chip8.memory_size()).then_some((address, end))
+}
+
+fn read_memory<R>(chip8: &Chip8<R>, arguments: &str) -> Option<String>
+where
+    R: RandomSource,
+{
+    let (start, end) = memory_range(chip8, arguments)?;
+    Some(encode_hex(&chip8.memory()[start..end]))
+}
+
+fn write_memory<R>(chip8: &mut Chip8<R>, arguments: &str) -> Option<()>
+where
+    R: RandomSource,
+{
+    let (range, data) = arguments.split_once(':')?;
+    let (start, end) = memory_range(chip8, range)?;
+    let bytes = decode_hex(data)?;
+    if bytes.len() != end - start {
+        return None;
+    }
+    chip8.memory_mut()[start..end].copy_from_slice(&bytes);
+    chip8.invalidate_decode_cache();
+    chip8.draw_flag = true;
+    Some(())
+}
+
+fn signal(number: u8) -> String {
+    format!("S{number:02x}")
+}
+
+fn ok() -> String {
+    "OK".to_string()
+}
+
+fn checksum_of(payload: &[u8]) -> u8 {
+    payload.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Session, State};
+    use rsc8_core::{chip8::Chip8, rng::FixedSequence};
+
+    fn new_chip8() -> Chip8<FixedSequence<'static>> {
+        let mut chip8 = Chip8::new(FixedSequence::new(&[0]));
+        // 0x200 LD V1, 0x2A, 0x202 LD I, 0x300, 0x204 LD [I], V1, 0x206 JP 0x206
+        chip8
+            .load_rom(&[0x61, 0x2A, 0xA3, 0x00, 0xF1, 0x55, 0x12, 0x06])
+            .unwrap();
+        chip8
+    }
+
+    #[test]
+    fn reads_and_writes_registers_and_memory() {
+        let mut chip8 = new_chip8();
+        let mut session = Session::new();
+        chip8.register_v[0] = 0x12;
+        chip8.register_i = 0x0345;
+
+        let registers = session.handle(&mut chip8, "g").unwrap();
+        assert_eq!(&registers[..4], "1200");
+        assert_eq!(&registers[32..40], "45030002");
+        assert_eq!(session.handle(&mut chip8, "p11"), Some("0002".to_string()));
+        assert_eq!(
+            session.handle(&mut chip8, "P10=3412"),
+            Some("OK".to_string())
+        );
+        assert_eq!(chip8.register_i, 0x1234);
+
+        assert_eq!(
+            session.handle(&mut chip8, "m200,4"),
+            Some("612aa300".to_string())
+        );
+        assert_eq!(
+            session.handle(&mut chip8, "M300,2:beef"),
+            Some("OK".to_string())
+        );
+        assert_eq!(chip8.memory[0x300..0x302], [0xBE, 0xEF]);
+        assert_eq!(
+            session.handle(&mut chip8, "mfff,2"),
+            Some("E01".to_string())
+        );
+    }
+
+    #[test]
+    fn continues_to_breakpoints_and_watchpoints() {
+        let mut chip8 = new_chip8();
+        let mut session = Session::new();
+        assert_eq!(
+            session.handle(&mut chip8, "Z0,202,2"),
+            Some("OK".to_string())
+        );
+        assert_eq!(
+            session.handle(&mut chip8, "Z2,300,1"),
+            Some("OK".to_string())
+        );
+
+        assert_eq!(session.handle(&mut chip8, "c"), None);
+        assert_eq!(session.run(&mut chip8, 100), Some("S05".to_string()));
+        assert_eq!(chip8.program_counter, 0x202);
+
+        session.handle(&mut chip8, "s");
+        assert!(session.state == State::Stepping);
+        assert_eq!(session.run(&mut chip8, 100), Some("S05".to_string()));
+        assert_eq!(chip8.program_counter, 0x204);
+
+        session.handle(&mut chip8, "c");
+        assert_eq!(
+            session.run(&mut chip8, 100),
+            Some("T05watch:300;".to_string())
+        );
+        assert_eq!(chip8.memory[0x301], 0x2A);
+
+        assert_eq!(
+            session.handle(&mut chip8, "z2,300,1"),
+            Some("OK".to_string())
+        );
+        session.handle(&mut chip8, "c");
+        assert_eq!(session.run(&mut chip8, 100), None);
+        assert_eq!(session.interrupt(), Some("S02".to_string()));
+    }
+
+    #[test]
+    fn resume_rejects_a_bad_address() {
+        let mut chip8 = new_chip8();
+        let mut session = Session::new();
+        assert_eq!(session.handle(&mut chip8, "cxyz"), Some("E01".to_string()));
+        assert_eq!(
+            session.handle(&mut chip8, "s10000"),
+            Some("E01".to_string())
+        );
+        assert_eq!(chip8.program_counter, 0x200);
+        assert!(session.state == State::Halted);
+
+        assert_eq!(session.handle(&mut chip8, "s204"), None);
+        assert_eq!(chip8.program_counter, 0x204);
+    }
+
+    #[test]
+    fn serves_the_target_description() {
+        let mut chip8 = new_chip8();
+        let mut session = Session::new();
+        let first = session
+            .handle(&mut chip8, "qXfer:features:read:target.xml:0,20")
+            .unwrap();
+        assert!(first.starts_with("m<?xml"));
+        let rest = session
+            .handle(&mut chip8, "qXfer:features:read:target.xml:20,1000")
+            .unwrap();
+        assert!(rest.starts_with('l') && rest.ends_with("</target>\n"));
+    }
+}
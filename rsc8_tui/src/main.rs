@@ -1,19 +1,38 @@
-use ratatui::{DefaultTerminal, crossterm::event, layout::Rect, style::Style, widgets::Block};
+mod args;
+mod gdb;
+mod movie;
+
+use args::{Args, Graph};
+use gdb::GdbServer;
+use movie::Movie;
+use ratatui::{DefaultTerminal, Frame, crossterm::event, style::Color};
+use rsc8_asm::assemble;
 use rsc8_core::{
-    chip8::{Chip8, SCREEN_WIDTH},
-    rng::LinearCongruentialGenerator,
+    analysis::analyze,
+    chip8::{Chip8, SCREEN_HEIGHT, SCREEN_WIDTH},
+    fontset::Fontset,
+    frame::{FrameConfig, FramePacing, FrameReport},
+    movie::{MovieHeader, font_hash, rom_hash},
+    rewind::Rewind,
+    rng::{LinearCongruentialGenerator, RandomSource},
+    timing::VipTiming,
+    trace::{TraceFilter, Tracer},
 };
 use std::{
-    env::args,
     error::Error,
-    fs::File,
-    io::Read,
+    fs::{self, File},
+    io::{BufWriter, Read, Write},
+    mem, process,
     time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 const FRAME_RATE: u64 = 60;
 const KEYPAD_RESET_COUNTDOWN_INIT: u64 = 10;
 const TICK_PER_FRAME: u8 = 8;
+const REWIND_FRAMES: usize = 10 * FRAME_RATE as usize;
+const REWIND_KEY: event::KeyCode = event::KeyCode::Backspace;
+// Indexed by the plane bits of a pixel
+const PLANE_COLORS: [Color; 4] = [Color::Reset, Color::White, Color::Yellow, Color::Red];
 const KEY_MAP: [(char, usize); 16] = [
     ('1', 0x1),
     ('2', 0x2),
@@ -34,73 +53,232 @@ const KEY_MAP: [(char, usize); 16] = [
 ];
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse()?;
+
+    // Read rom, assembling Octo sources first
+    let mut rom = Vec::new();
+    File::open(&args.rom_path)?.read_to_end(&mut rom)?;
+    if args.rom_path.ends_with(".8o") {
+        let source = String::from_utf8(rom)?;
+        rom = assemble(&source).map_err(|error| format!("{}:{error}", args.rom_path))?;
+    }
+
+    // A font file replaces the small font, and the big one if it has both
+    let mut fontset = args.fontset;
+    if let Some(path) = &args.font_path {
+        fontset
+            .load_bytes(&fs::read(path)?)
+            .map_err(|error| format!("{path}: {error}"))?;
+    }
+
+    if let Some(syntax) = args.disassemble {
+        let analysis = analyze(&rom, args.config.program_start)?;
+        let disassembly = analysis.disassemble(syntax);
+        print!("{}", analysis.annotate(&disassembly));
+        return Ok(());
+    }
+    if let Some(graph) = args.dot {
+        let analysis = analyze(&rom, args.config.program_start)?;
+        let mut dot = String::new();
+        match graph {
+            Graph::ControlFlow => analysis.write_dot(&mut dot)?,
+            Graph::Calls => analysis.write_call_graph_dot(&mut dot)?,
+        }
+        print!("{dot}");
+        return Ok(());
+    }
+
+    // Wait for the debugger before taking over the terminal
+    let gdb = args.gdb_port.map(GdbServer::accept).transpose()?;
+
     let mut terminal = ratatui::init();
     terminal.clear().unwrap();
-    let result = run(terminal);
+    let result = run(terminal, args, rom, fontset, gdb);
     ratatui::restore();
-    result
+    // Errors returned from main are printed with Debug, print the full
+    // context of a crash through Display instead
+    if let Err(error) = result {
+        eprintln!("Error: {error}");
+        process::exit(1);
+    }
+    Ok(())
 }
 
-fn run(mut terminal: DefaultTerminal) -> Result<(), Box<dyn Error>> {
+fn run(
+    mut terminal: DefaultTerminal,
+    args: Args,
+    rom: Vec<u8>,
+    fontset: Fontset,
+    mut gdb: Option<GdbServer>,
+) -> Result<(), Box<dyn Error>> {
     // Init rng
-    let rng = match SystemTime::now().duration_since(UNIX_EPOCH) {
+    let mut rng = match SystemTime::now().duration_since(UNIX_EPOCH) {
         Ok(unix_timestamp) => LinearCongruentialGenerator {
             seed: unix_timestamp.as_millis() as u16,
         },
         Err(_) => LinearCongruentialGenerator::default(),
     };
 
+    // Init movie, a replay also restores the recorded machine settings and
+    // frame timing
+    let mut header = MovieHeader {
+        rng_state: rng.state(),
+        rom_hash: rom_hash(&rom),
+        quirks: args.quirks,
+        config: args.config,
+        vip_timing: args.vip_timing,
+        font_hash: font_hash(&fontset),
+    };
+    let mut movie = if let Some(path) = &args.replay_path {
+        let (movie, recorded) = Movie::load(path, header.rom_hash, header.font_hash)?;
+        header = recorded;
+        rng.set_state(header.rng_state);
+        movie
+    } else if let Some(path) = &args.record_path {
+        Movie::Record {
+            path: path.clone(),
+            header,
+            frames: Vec::new(),
+        }
+    } else {
+        Movie::Off
+    };
+
     // Init chip8
-    let mut chip8 = Chip8::new(rng);
+    let mut chip8 = Chip8::with_config(rng, header.quirks, header.config)?;
+    chip8.enable_decode_cache();
 
     // Load fontset
-    chip8.load_fontset();
+    chip8.load_custom_fontset(&fontset);
 
     // Load rom
+    chip8.load_rom(&rom)?;
+
+    // Init trace
+    let mut trace = match &args.trace_path {
+        Some(path) => Some(Trace {
+            tracer: Tracer::new(TraceFilter {
+                start_cycle: args.trace_start,
+                stop_cycle: args.trace_stop,
+                ranges: &args.trace_ranges,
+            }),
+            file: BufWriter::new(File::create(path)?),
+            line: String::new(),
+        }),
+        None => None,
+    };
+
+    let result = run_loop(
+        &mut terminal,
+        &mut chip8,
+        &mut movie,
+        gdb.as_mut(),
+        trace.as_mut(),
+        // Either a fixed instruction count or the VIP cycle budget
+        FrameConfig {
+            skip_idle_loops: true,
+            ..FrameConfig::new(if header.vip_timing {
+                FramePacing::VipTiming(VipTiming::new())
+            } else {
+                FramePacing::Ticks(TICK_PER_FRAME as usize)
+            })
+        },
+    );
+    if let Some(trace) = trace.as_mut() {
+        trace.file.flush()?;
+    }
+    movie.save()?;
+    result
+}
+
+struct Trace<'a> {
+    tracer: Tracer<'a>,
+    file: BufWriter<File>,
+    line: String,
+}
+
+impl Trace<'_> {
+    fn record<R>(&mut self, chip8: &Chip8<R>) -> Result<(), Box<dyn Error>>
+    where
+        R: RandomSource,
     {
-        let filename = args().nth(1).ok_or("Usage: rsc8_tui <your_rom.ch8>")?;
-        let mut f = File::open(filename)?;
-        let mut buffer = Vec::new();
-        f.read_to_end(&mut buffer)?;
-        chip8.load_rom(&buffer)?;
+        if self.tracer.is_done() {
+            return Ok(());
+        }
+        self.line.clear();
+        self.tracer.trace(chip8, &mut self.line)?;
+        self.file.write_all(self.line.as_bytes())?;
+        Ok(())
     }
+}
 
+fn run_loop(
+    terminal: &mut DefaultTerminal,
+    chip8: &mut Chip8<LinearCongruentialGenerator>,
+    movie: &mut Movie,
+    mut gdb: Option<&mut GdbServer>,
+    mut trace: Option<&mut Trace>,
+    mut frame_config: FrameConfig,
+) -> Result<(), Box<dyn Error>> {
     let tick_rate = Duration::from_millis(1000 / FRAME_RATE);
     let mut last_tick = Instant::now();
 
     let mut keypad_reset_countdown = KEYPAD_RESET_COUNTDOWN_INIT;
 
+    let mut rewind = Rewind::new(REWIND_FRAMES);
+    let mut rewinding = false;
+    rewind.record(chip8)?;
+
     loop {
-        // Tick
-        for _ in 0..TICK_PER_FRAME {
-            if chip8.wait_for_key_release.is_some() {
-                break;
+        // Serve the debugger, the machine is frozen while it is halted
+        if let Some(gdb) = gdb.as_deref_mut() {
+            gdb.poll(chip8)?;
+            if gdb.is_killed() {
+                return Ok(());
             }
-            chip8.tick()?;
         }
+        let halted = gdb.as_deref().is_some_and(GdbServer::is_halted);
 
-        // Tick timer
-        chip8.tick_timer();
+        let mut redraw = false;
+        if halted {
+            // Wait for the debugger
+        } else if rewinding {
+            // Rewind
+            let stepped = rewind.step_back(chip8, 1)?;
+            if stepped > 0 {
+                movie.step_back(stepped);
+                redraw = true;
+            }
+        } else {
+            // Record or replay input
+            movie.next_frame(chip8);
+
+            // Tick, under the debugger instruction by instruction
+            if let Some(gdb) = gdb.as_deref_mut() {
+                gdb.run(chip8, TICK_PER_FRAME as usize)?;
+                chip8.tick_timer();
+                redraw = mem::take(&mut chip8.draw_flag);
+            } else {
+                let report = run_frame(chip8, &mut frame_config, trace.as_deref_mut())?;
+                // Beep
+                if report.sound_started {
+                    print!("\x07");
+                }
+                redraw = report.screen_changed;
+            }
 
-        // Beep
-        if chip8.sound_timer > 0 {
-            print!("\x07");
+            // Record rewind history
+            rewind.record(chip8)?;
         }
 
         // Draw screen
-        if chip8.draw_flag {
-            chip8.draw_flag = false;
-            terminal.draw(|frame| {
-                chip8.screen.iter().enumerate().for_each(|(index, pixel)| {
-                    if *pixel {
-                        let x = (index % SCREEN_WIDTH) as u16;
-                        let y = (index / SCREEN_WIDTH) as u16;
-                        let area = Rect::new(x * 2, y, 2, 1);
-                        let block = Block::default().style(Style::new().on_white());
-                        frame.render_widget(block, area);
-                    }
-                });
-            })?;
+        if redraw {
+            terminal.draw(|frame| draw_screen(frame, chip8))?;
+        }
+
+        // Exit
+        if chip8.exited {
+            return Ok(());
         }
 
         // Update keypad
@@ -111,27 +289,25 @@ fn run(mut terminal: DefaultTerminal) -> Result<(), Box<dyn Error>> {
                 if key_event.code == event::KeyCode::Esc {
                     return Ok(());
                 }
-                if let Some(chip8_key_code) = pc_key_code_to_chip8_key_code(&key_event.code) {
+                if key_event.code == REWIND_KEY {
+                    rewinding = key_event.kind != event::KeyEventKind::Release;
+                }
+                if let Some(chip8_key_code) = pc_key_code_to_chip8_key_code(&key_event.code)
+                    && !movie.is_replaying()
+                {
                     match key_event.kind {
-                        event::KeyEventKind::Press => chip8.keypad[chip8_key_code] = true,
-                        event::KeyEventKind::Release => {
-                            chip8.keypad[chip8_key_code] = false;
-                            if let Some(key_code) = chip8.wait_for_key_release
-                                && chip8_key_code == key_code
-                            {
-                                chip8.wait_for_key_release = None;
-                            }
-                        }
+                        event::KeyEventKind::Press => chip8.key_down(chip8_key_code),
+                        event::KeyEventKind::Release => chip8.key_up(chip8_key_code),
                         event::KeyEventKind::Repeat => {}
                     }
                 }
             }
-        } else if !cfg!(windows) {
+        } else if !cfg!(windows) && !movie.is_replaying() {
             keypad_reset_countdown -= 1;
             if keypad_reset_countdown == 0 {
                 keypad_reset_countdown = KEYPAD_RESET_COUNTDOWN_INIT;
-                chip8.keypad.iter_mut().for_each(|pressed| *pressed = false);
-                chip8.wait_for_key_release = None;
+                chip8.set_keys(0);
+                rewinding = false;
             }
         }
 
@@ -140,6 +316,53 @@ fn run(mut terminal: DefaultTerminal) -> Result<(), Box<dyn Error>> {
     }
 }
 
+// Runs one frame, tracing every instruction when enabled
+fn run_frame(
+    chip8: &mut Chip8<LinearCongruentialGenerator>,
+    config: &mut FrameConfig,
+    trace: Option<&mut Trace>,
+) -> Result<FrameReport, Box<dyn Error>> {
+    let mut traced = Ok(());
+    let mut report = match trace {
+        Some(trace) => chip8.run_frame_with(config, |chip8| {
+            if traced.is_ok() {
+                traced = trace.record(chip8);
+            }
+        }),
+        None => chip8.run_frame(config),
+    };
+    traced?;
+    match report.error.take() {
+        Some(error) => Err(error.into()),
+        None => Ok(report),
+    }
+}
+
+// Both resolutions fill 128x32 terminal cells: a lores pixel is two cells wide,
+// a hires pixel is half a cell tall.
+fn draw_screen<R>(frame: &mut Frame, chip8: &Chip8<R>)
+where
+    R: RandomSource,
+{
+    let pixel = |x: usize, y: usize| PLANE_COLORS[chip8.screen.pixel(x, y) as usize];
+    let area = frame.area();
+    let buffer = frame.buffer_mut();
+    for cell_y in 0..(SCREEN_HEIGHT as u16 / 2).min(area.height) {
+        for cell_x in 0..(SCREEN_WIDTH as u16).min(area.width) {
+            let (top, bottom) = if chip8.hires {
+                let (x, y) = (cell_x as usize, cell_y as usize * 2);
+                (pixel(x, y), pixel(x, y + 1))
+            } else {
+                let color = pixel(cell_x as usize / 2, cell_y as usize);
+                (color, color)
+            };
+            if let Some(cell) = buffer.cell_mut((cell_x, cell_y)) {
+                cell.set_symbol("▀").set_fg(top).set_bg(bottom);
+            }
+        }
+    }
+}
+
 fn pc_key_code_to_chip8_key_code(key_code: &event::KeyCode) -> Option<usize> {
     if let event::KeyCode::Char(c) = key_code {
         KEY_MAP
@@ -0,0 +1,95 @@
+use rsc8_core::{
+    chip8::Chip8,
+    movie::{MOVIE_HEADER_SIZE, MovieHeader, parse_movie},
+    rng::RandomSource,
+};
+use std::{error::Error, fs, io};
+
+pub enum Movie {
+    Off,
+    Record {
+        path: String,
+        header: MovieHeader,
+        frames: Vec<u16>,
+    },
+    Replay {
+        frames: Vec<u16>,
+        position: usize,
+    },
+}
+
+impl Movie {
+    pub fn load(
+        path: &str,
+        rom_hash: u64,
+        font_hash: u64,
+    ) -> Result<(Self, MovieHeader), Box<dyn Error>> {
+        let buffer = fs::read(path)?;
+        let (header, frames) = parse_movie(&buffer)?;
+        if header.rom_hash != rom_hash {
+            return Err(format!("{path} was recorded with a different ROM").into());
+        }
+        if header.font_hash != font_hash {
+            return Err(format!("{path} was recorded with a different font").into());
+        }
+        let movie = Movie::Replay {
+            frames: frames.collect(),
+            position: 0,
+        };
+        Ok((movie, header))
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        matches!(self, Movie::Replay { .. })
+    }
+
+    // Called once at the start of every emulated frame
+    pub fn next_frame<R>(&mut self, chip8: &mut Chip8<R>)
+    where
+        R: RandomSource,
+    {
+        match self {
+            Movie::Off => {}
+            Movie::Record { frames, .. } => frames.push(chip8.keypad_bitmask()),
+            Movie::Replay { frames, position } => match frames.get(*position) {
+                Some(&keypad) => {
+                    chip8.set_keys(keypad);
+                    *position += 1;
+                }
+                // Hand control back to the player when the movie ends
+                None => *self = Movie::Off,
+            },
+        }
+    }
+
+    // Keeps the movie in sync with the rewind buffer, so recording after a
+    // rewind overwrites the frames that were undone
+    pub fn step_back(&mut self, frames: usize) {
+        match self {
+            Movie::Off => {}
+            Movie::Record { frames: inputs, .. } => {
+                inputs.truncate(inputs.len().saturating_sub(frames));
+            }
+            Movie::Replay { position, .. } => *position = position.saturating_sub(frames),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Movie::Record {
+            path,
+            header,
+            frames,
+        } = self
+        {
+            let mut buffer = vec![0; MOVIE_HEADER_SIZE];
+            header
+                .write(&mut buffer)
+                .map_err(|error| io::Error::other(error.to_string()))?;
+            frames
+                .iter()
+                .for_each(|keypad| buffer.extend_from_slice(&keypad.to_le_bytes()));
+            fs::write(path, buffer)?;
+        }
+        Ok(())
+    }
+}